@@ -0,0 +1,99 @@
+// Headless benchmarks for lots of falling objects: `cargo bench`.
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use interactive_drawing::broadphase::SpatialHash;
+use interactive_drawing::collision::Rect;
+use interactive_drawing::input::InputFrame;
+use interactive_drawing::spawner::Kind;
+use interactive_drawing::world::World;
+
+// A run in progress with n things scattered all over the screen
+fn crowded_world(n: usize) -> World {
+    let mut world = World::new(1);
+    world.step(&InputFrame { start: true, ..Default::default() });
+    // nobody should lose in the middle of a benchmark
+    world.gs.lives = u32::MAX;
+    let mut rng = ChaCha8Rng::seed_from_u64(2);
+    for i in 0..n {
+        let kind = if i % 4 == 0 { Kind::Hazard } else { Kind::Acorn };
+        world.falling.spawn(kind, &mut world.gs.rng);
+        let body = &mut world.falling.objects[i].body;
+        body.screen_region[1] = rng.gen_range(0.0..world.size.height);
+    }
+    // and the squirrel somewhere in the thick of it
+    world.squirrel.screen_region[0] = 500.0;
+    world.squirrel.screen_region[1] = 300.0;
+    world
+}
+
+// Acorn-sized rects, about one per 100x100px however many there are, like a big level would have
+fn scattered_rects(n: usize) -> Vec<Rect> {
+    let mut rng = ChaCha8Rng::seed_from_u64(3);
+    let side = (n as f32).sqrt() * 100.0;
+    (0..n)
+        .map(|_| Rect::new(rng.gen_range(0.0..side), rng.gen_range(0.0..side), 55.0, 55.0))
+        .collect()
+}
+
+fn world_step(c: &mut Criterion) {
+    let mut group = c.benchmark_group("world step");
+    for n in [1_000, 5_000] {
+        group.bench_with_input(BenchmarkId::from_parameter(n), &n, |b, &n| {
+            b.iter_batched(
+                || crowded_world(n),
+                |mut world| world.step(&InputFrame { left: true, ..Default::default() }),
+                BatchSize::LargeInput,
+            );
+        });
+    }
+    group.finish();
+}
+
+// Just the part of a step that finds what the squirrel is touching: filling
+// the spatial hash with every hitbox and looking up the squirrel's
+fn squirrel_touching(c: &mut Criterion) {
+    let mut group = c.benchmark_group("squirrel touching");
+    for n in [1_000, 5_000] {
+        let mut world = crowded_world(n);
+        group.bench_function(BenchmarkId::from_parameter(n), |b| b.iter(|| world.squirrel_touching()));
+    }
+    group.finish();
+}
+
+// Finding every overlapping pair, with and without the grid
+fn all_pairs(c: &mut Criterion) {
+    let mut group = c.benchmark_group("overlapping pairs");
+    for n in [1_000, 5_000] {
+        let rects = scattered_rects(n);
+        group.bench_with_input(BenchmarkId::new("spatial hash", n), &rects, |b, rects| {
+            let mut hash = SpatialHash::new(128.0);
+            b.iter(|| {
+                hash.clear();
+                for (i, rect) in rects.iter().enumerate() {
+                    hash.insert(i, rect);
+                }
+                hash.pairs().into_iter().filter(|&(i, j)| rects[i].overlaps(&rects[j])).count()
+            });
+        });
+        group.bench_with_input(BenchmarkId::new("every pair", n), &rects, |b, rects| {
+            b.iter(|| {
+                let mut count = 0;
+                for i in 0..rects.len() {
+                    for j in i + 1..rects.len() {
+                        if rects[i].overlaps(&rects[j]) {
+                            count += 1;
+                        }
+                    }
+                }
+                count
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, world_step, squirrel_touching, all_pairs);
+criterion_main!(benches);
@@ -1,3 +1,6 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Animation {
     // states are sprite sheet positions
     pub(crate) states: Vec<[f32; 4]>,
@@ -29,7 +32,7 @@ impl Animation {
             self.tick();
         }
     }
-    pub fn get_current_state(&mut self) -> [f32; 4]{
+    pub fn get_current_state(&self) -> [f32; 4]{
         return self.states[self.state_number]
     }
 }
\ No newline at end of file
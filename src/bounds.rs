@@ -0,0 +1,69 @@
+use serde::{Deserialize, Serialize};
+
+// How big the world is, in the same units as screen regions.  Movement,
+// spawning and the camera all go by this, so it's the one place to change it.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WorldSize {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for WorldSize {
+    fn default() -> Self {
+        WorldSize { width: 1024.0, height: 768.0 }
+    }
+}
+
+// What happens when something reaches the side of the world
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Edges {
+    // stop at the edge, staying all the way on screen
+    Clamp,
+    // go off one side and come back in on the other.  Top and bottom still clamp.
+    Wrap,
+}
+
+// How far to move something spanning lo..hi so it's inside 0..max.  Anything
+// wider than that lines up on the left.
+pub fn clamp_shift(lo: f32, hi: f32, max: f32) -> f32 {
+    if lo < 0.0 {
+        -lo
+    } else if hi > max {
+        (max - hi).max(-lo)
+    } else {
+        0.0
+    }
+}
+
+// How far to move something centered at center so it's back inside 0..max,
+// coming in from the other side
+pub fn wrap_shift(center: f32, max: f32) -> f32 {
+    if center < 0.0 {
+        max
+    } else if center >= max {
+        -max
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clamping_pulls_things_back_in() {
+        assert_eq!(clamp_shift(-10.0, 45.0, 1024.0), 10.0);
+        assert_eq!(clamp_shift(1000.0, 1055.0, 1024.0), -31.0);
+        assert_eq!(clamp_shift(0.0, 1024.0, 1024.0), 0.0);
+        // too wide to fit either way
+        assert_eq!(clamp_shift(5.0, 2005.0, 1024.0), -5.0);
+    }
+
+    #[test]
+    fn wrapping_comes_in_the_other_side() {
+        assert_eq!(wrap_shift(-1.0, 1024.0), 1024.0);
+        assert_eq!(wrap_shift(1024.0, 1024.0), -1024.0);
+        assert_eq!(wrap_shift(512.0, 1024.0), 0.0);
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+
+use crate::collision::Rect;
+
+// A uniform grid over screen space.  Each rect goes into every cell it touches,
+// so two rects can only overlap if they share a cell, and a query only has to
+// look at the handful of cells under it instead of at everything.  What comes
+// out are candidates: they still need a real Rect::overlaps check.
+pub struct SpatialHash {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl SpatialHash {
+    // cell_size should be about the size of the things going in
+    pub fn new(cell_size: f32) -> SpatialHash {
+        SpatialHash {
+            cell_size,
+            cells: HashMap::new(),
+        }
+    }
+
+    // Empty it out for the next step, but hang on to the memory
+    pub fn clear(&mut self) {
+        for ids in self.cells.values_mut() {
+            ids.clear();
+        }
+    }
+
+    pub fn insert(&mut self, id: usize, rect: &Rect) {
+        let (min, max) = self.cell_range(rect);
+        for cx in min.0..=max.0 {
+            for cy in min.1..=max.1 {
+                self.cells.entry((cx, cy)).or_default().push(id);
+            }
+        }
+    }
+
+    // Everything that might overlap rect, in order, each only once
+    pub fn query(&self, rect: &Rect) -> Vec<usize> {
+        let (min, max) = self.cell_range(rect);
+        let mut found = vec![];
+        for cx in min.0..=max.0 {
+            for cy in min.1..=max.1 {
+                if let Some(ids) = self.cells.get(&(cx, cy)) {
+                    found.extend_from_slice(ids);
+                }
+            }
+        }
+        found.sort_unstable();
+        found.dedup();
+        found
+    }
+
+    // Every pair of things that share a cell, as (smaller id, bigger id), each only once.
+    // This is for checking objects against each other rather than against the squirrel.
+    pub fn pairs(&self) -> Vec<(usize, usize)> {
+        let mut pairs = vec![];
+        for ids in self.cells.values() {
+            for (i, &a) in ids.iter().enumerate() {
+                for &b in &ids[i + 1..] {
+                    pairs.push((a.min(b), a.max(b)));
+                }
+            }
+        }
+        pairs.sort_unstable();
+        pairs.dedup();
+        pairs
+    }
+
+    // The first and last cells a rect covers.  floor keeps negative coordinates
+    // in the right cells.
+    fn cell_range(&self, rect: &Rect) -> ((i32, i32), (i32, i32)) {
+        let cell = |v: f32| (v / self.cell_size).floor() as i32;
+        ((cell(rect.left()), cell(rect.bottom())), (cell(rect.right()), cell(rect.top())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    fn random_rects(n: usize, seed: u64) -> Vec<Rect> {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        (0..n)
+            .map(|_| {
+                let (x, y) = (rng.gen_range(-200.0..1200.0), rng.gen_range(-200.0..900.0));
+                // some of them flipped, like a sprite facing the other way
+                let w = if rng.gen() { 55.0 } else { -55.0 };
+                Rect::new(x, y, w, rng.gen_range(10.0..80.0))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn finds_the_same_overlaps_as_checking_everything() {
+        let rects = random_rects(400, 5);
+        let mut hash = SpatialHash::new(64.0);
+        for (i, rect) in rects.iter().enumerate() {
+            hash.insert(i, rect);
+        }
+
+        let mut brute_force = vec![];
+        for i in 0..rects.len() {
+            for j in i + 1..rects.len() {
+                if rects[i].overlaps(&rects[j]) {
+                    brute_force.push((i, j));
+                }
+            }
+        }
+        let narrowed: Vec<(usize, usize)> = hash
+            .pairs()
+            .into_iter()
+            .filter(|&(i, j)| rects[i].overlaps(&rects[j]))
+            .collect();
+        assert!(!brute_force.is_empty());
+        assert_eq!(narrowed, brute_force);
+
+        let squirrel = Rect::new(300.0, 32.0, 100.0, 75.0);
+        let hits: Vec<usize> = hash.query(&squirrel).into_iter().filter(|&i| rects[i].overlaps(&squirrel)).collect();
+        let expected: Vec<usize> = (0..rects.len()).filter(|&i| rects[i].overlaps(&squirrel)).collect();
+        assert_eq!(hits, expected);
+    }
+
+    #[test]
+    fn clearing_forgets_everything() {
+        let mut hash = SpatialHash::new(64.0);
+        hash.insert(0, &Rect::new(0.0, 0.0, 10.0, 10.0));
+        hash.insert(1, &Rect::new(5.0, 5.0, 10.0, 10.0));
+        assert_eq!(hash.pairs(), vec![(0, 1)]);
+        hash.clear();
+        assert!(hash.pairs().is_empty());
+        assert!(hash.query(&Rect::new(0.0, 0.0, 100.0, 100.0)).is_empty());
+    }
+}
@@ -0,0 +1,184 @@
+use crate::bounds::WorldSize;
+
+// how quickly the camera catches up with what it's following: the gap shrinks
+// by about this fraction of itself every 1/FOLLOW_RATE seconds
+const FOLLOW_RATE: f32 = 4.0;
+// how far zoom can go either way; 1.0 shows view_size
+pub const MIN_ZOOM: f32 = 1.0;
+pub const MAX_ZOOM: f32 = 3.0;
+// how much one press of + or - zooms by
+pub const ZOOM_STEP: f32 = 1.25;
+// how much trauma wears off per second
+const TRAUMA_DECAY: f32 = 1.5;
+// the furthest a shake can throw the camera, in world pixels, at full trauma
+const MAX_SHAKE: f32 = 16.0;
+// how much trauma a pinecone to the head adds
+pub const HIT_TRAUMA: f32 = 0.6;
+
+// What part of the world is on screen.  It follows a target around, stays
+// inside the world's bounds, zooms, and shakes when it's given some trauma.
+// Like the World, it doesn't know about the GPU; main hands screen_pos and
+// screen_size over to the sprite renderer every frame.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Camera {
+    // the bottom left of what's on screen, before any shake
+    pub pos: [f32; 2],
+    // how much of the world fits on screen at zoom 1.0
+    pub view_size: [f32; 2],
+    // bigger is closer in; between MIN_ZOOM and MAX_ZOOM
+    pub zoom: f32,
+    // 0.0 to 1.0; the shake goes with the square of it, so small knocks barely show
+    pub trauma: f32,
+    // seconds of updates so far, to move the shake along
+    time: f32,
+}
+
+impl Camera {
+    pub fn new(view_size: [f32; 2]) -> Camera {
+        Camera { pos: [0.0, 0.0], view_size, zoom: MIN_ZOOM, trauma: 0.0, time: 0.0 }
+    }
+
+    // The world-sized area on screen, which gets smaller as we zoom in
+    pub fn screen_size(&self) -> [f32; 2] {
+        [self.view_size[0] / self.zoom, self.view_size[1] / self.zoom]
+    }
+
+    // Where the bottom left of the screen is, shake included
+    pub fn screen_pos(&self) -> [f32; 2] {
+        let [dx, dy] = self.shake();
+        [self.pos[0] + dx, self.pos[1] + dy]
+    }
+
+    // Zoom in or out, keeping the middle of the screen where it is
+    pub fn set_zoom(&mut self, zoom: f32) {
+        let [w, h] = self.screen_size();
+        let center = [self.pos[0] + w / 2.0, self.pos[1] + h / 2.0];
+        self.zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+        let [w, h] = self.screen_size();
+        self.pos = [center[0] - w / 2.0, center[1] - h / 2.0];
+    }
+
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).min(1.0);
+    }
+
+    // Ease towards having target (a point in the world) in the middle of the
+    // screen without showing anything past the edges of bounds
+    pub fn update(&mut self, target: (f32, f32), bounds: WorldSize, dt: f32) {
+        let [w, h] = self.screen_size();
+        let goal = [
+            clamp_view(target.0 - w / 2.0, w, bounds.width),
+            clamp_view(target.1 - h / 2.0, h, bounds.height),
+        ];
+        // the same fraction of the way there each second, however long the steps are
+        let t = 1.0 - (-FOLLOW_RATE * dt).exp();
+        for (pos, goal) in self.pos.iter_mut().zip(goal) {
+            *pos += (goal - *pos) * t;
+        }
+        // zooming can leave us past an edge, and that shouldn't ease back in
+        self.pos = [clamp_view(self.pos[0], w, bounds.width), clamp_view(self.pos[1], h, bounds.height)];
+        self.trauma = (self.trauma - TRAUMA_DECAY * dt).max(0.0);
+        self.time += dt;
+    }
+
+    // How far the shake has the camera off from pos right now.  Different
+    // wobbles on each axis, so it doesn't just go back and forth diagonally.
+    fn shake(&self) -> [f32; 2] {
+        let amount = MAX_SHAKE * self.trauma * self.trauma;
+        let t = self.time;
+        [
+            amount * ((t * 47.0).sin() + 0.5 * (t * 83.0 + 1.0).sin()) / 1.5,
+            amount * ((t * 53.0 + 2.0).sin() + 0.5 * (t * 71.0 + 3.0).sin()) / 1.5,
+        ]
+    }
+
+    // Where a point in the world shows up in a window this many pixels big,
+    // counting from the top left like the mouse and the text do
+    pub fn world_to_screen(&self, world: [f32; 2], window: [f32; 2]) -> [f32; 2] {
+        let [x, y] = self.screen_pos();
+        let [w, h] = self.screen_size();
+        [(world[0] - x) / w * window[0], (1.0 - (world[1] - y) / h) * window[1]]
+    }
+
+    // The other way around, like for finding what the mouse is pointing at
+    pub fn screen_to_world(&self, screen: [f32; 2], window: [f32; 2]) -> [f32; 2] {
+        let [x, y] = self.screen_pos();
+        let [w, h] = self.screen_size();
+        [x + screen[0] / window[0] * w, y + (1.0 - screen[1] / window[1]) * h]
+    }
+}
+
+// Keep a view this big starting at pos inside 0..max, or in the middle if it
+// doesn't fit
+fn clamp_view(pos: f32, size: f32, max: f32) -> f32 {
+    if size >= max {
+        (max - size) / 2.0
+    } else {
+        pos.clamp(0.0, max - size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORLD: WorldSize = WorldSize { width: 1024.0, height: 768.0 };
+
+    #[test]
+    fn follows_smoothly_but_stays_in_bounds() {
+        let mut camera = Camera::new([1024.0, 768.0]);
+        camera.set_zoom(2.0);
+        assert_eq!(camera.screen_size(), [512.0, 384.0]);
+        // zoomed in on the middle
+        assert_eq!(camera.pos, [256.0, 192.0]);
+
+        camera.update((700.0, 400.0), WORLD, 1.0 / 60.0);
+        assert!(camera.pos[0] > 256.0 && camera.pos[0] < 700.0 - 256.0);
+        for _ in 0..600 {
+            camera.update((700.0, 400.0), WORLD, 1.0 / 60.0);
+        }
+        assert!((camera.pos[0] - (700.0 - 256.0)).abs() < 0.01);
+
+        // never past the corner of the world
+        for _ in 0..600 {
+            camera.update((0.0, 0.0), WORLD, 1.0 / 60.0);
+        }
+        assert!(camera.pos.iter().all(|&p| (0.0..0.01).contains(&p)), "{:?}", camera.pos);
+        // and all of it at zoom 1.0
+        camera.set_zoom(0.5);
+        assert_eq!(camera.zoom, MIN_ZOOM);
+        camera.update((1000.0, 700.0), WORLD, 1.0 / 60.0);
+        assert_eq!(camera.pos, [0.0, 0.0]);
+    }
+
+    #[test]
+    fn trauma_shakes_then_settles() {
+        let mut camera = Camera::new([1024.0, 768.0]);
+        camera.add_trauma(HIT_TRAUMA);
+        camera.add_trauma(HIT_TRAUMA);
+        assert_eq!(camera.trauma, 1.0);
+        let mut moved = false;
+        for _ in 0..60 {
+            camera.update((512.0, 384.0), WORLD, 1.0 / 60.0);
+            moved |= camera.screen_pos() != camera.pos;
+            let [dx, dy] = [camera.screen_pos()[0] - camera.pos[0], camera.screen_pos()[1] - camera.pos[1]];
+            assert!(dx.abs() <= MAX_SHAKE && dy.abs() <= MAX_SHAKE);
+        }
+        assert!(moved);
+        assert_eq!(camera.trauma, 0.0);
+        assert_eq!(camera.screen_pos(), camera.pos);
+    }
+
+    #[test]
+    fn world_and_screen_convert_both_ways() {
+        let mut camera = Camera::new([1024.0, 768.0]);
+        camera.set_zoom(2.0);
+        let window = [800.0, 600.0];
+        // the bottom left of the view is the bottom left of the window
+        assert_eq!(camera.world_to_screen(camera.pos, window), [0.0, 600.0]);
+        assert_eq!(camera.screen_to_world([400.0, 300.0], window), [512.0, 384.0]);
+        let point = [300.0, 500.0];
+        let back = camera.screen_to_world(camera.world_to_screen(point, window), window);
+        assert!((back[0] - point[0]).abs() < 0.001 && (back[1] - point[1]).abs() < 0.001);
+    }
+}
@@ -1,11 +1,35 @@
 use rand::Rng;
-use crate::Animation;
+use serde::{Deserialize, Serialize};
+use crate::animation::Animation;
+use crate::bounds::{clamp_shift, wrap_shift, Edges, WorldSize};
+use crate::collision::{Hitbox, Rect};
+use crate::physics::Physics;
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Char_action {
     pub screen_region: [f32; 4],
+    // where screen_region was at the start of the last step, for interpolating
+    pub prev_screen_region: [f32; 4],
     pub animation: Animation,
     pub speed: f32,
     pub facing_right: bool,
+    // draw mirrored left to right; the sheet's squirrel faces left, so facing
+    // right means flipping it.  The region itself never changes.
+    pub flip_x: bool,
     pub sprites_index: usize,
+    // the part of the sprite that counts for collisions
+    pub hitbox: Hitbox,
+    // only the squirrel uses this; falling things just move_down
+    pub physics: Physics,
+    // what happens at the sides of the world
+    pub edges: Edges,
+    // multiplied into the sprite's colors; the last one is opacity
+    pub tint: [f32; 4],
+    // radians counterclockwise around the middle, and how fast that changes
+    // (per second) while falling
+    pub rotation: f32,
+    pub spin: f32,
+    // drawn this many times bigger around the middle; collisions don't change
+    pub scale: f32,
 }
 
 impl Char_action {
@@ -16,46 +40,113 @@ impl Char_action {
         facing_rig: bool,
         sprites_ind: usize,) -> Char_action {
             Self { screen_region: (screen_re), 
+                prev_screen_region: (screen_re), 
                 animation: (anim), 
                 speed: (spe), 
                 facing_right: (facing_rig), 
-                sprites_index: (sprites_ind) }
+                flip_x: (facing_rig),
+                sprites_index: (sprites_ind),
+                hitbox: Hitbox::FULL,
+                physics: Physics::default(),
+                edges: Edges::Clamp,
+                tint: [1.0; 4],
+                rotation: 0.0,
+                spin: 0.0,
+                scale: 1.0 }
     }
 
-    pub fn walk(&mut self){
+    // speed is in pixels per second and dt is the length of the step in seconds
+    pub fn walk(&mut self, dt: f32){
         if self.facing_right {
-            self.screen_region[0] += self.speed;
+            self.screen_region[0] += self.speed * dt;
         }
         // if facing left
         else {
-            self.screen_region[0] -= self.speed;
+            self.screen_region[0] -= self.speed * dt;
         }
     }
     pub fn face_left(&mut self) {
         self.facing_right = false;
-        if self.screen_region[2] < 0.0 {
-            self.screen_region[2] *= -1.0;
-            self.screen_region[0] -= 60.0;
-        }
-        
+        self.flip_x = false;
     }
     pub fn face_right(&mut self) {
         self.facing_right = true;
-        if self.screen_region[2] > 0.0 {
-            self.screen_region[2] *= -1.0;
-            self.screen_region[0] += 60.0;
+        self.flip_x = true;
+    }
+    // returns true once we've hit the ground; what happens then is up to the caller
+    pub fn move_down(&mut self, dt: f32) -> bool {
+        self.screen_region[1] -= self.speed * dt;
+        self.rotation += self.spin * dt;
+        self.screen_region[1] <= 0.0
+    }
+    // Let gravity and any jump move us, landing on ground (the height of whatever's underneath)
+    pub fn apply_physics(&mut self, ground: f32, dt: f32) {
+        let mut pos = [self.screen_region[0], self.screen_region[1]];
+        self.physics.step(&mut pos, ground, dt);
+        self.screen_region[0] = pos[0];
+        self.screen_region[1] = pos[1];
+    }
+    // Back up to the top of the world, somewhere between x_range's left and right
+    // but never hanging off either side
+    pub fn reset_y(&mut self, size: WorldSize, x_range: (f32, f32), rng: &mut impl Rng){
+        let max_x = (size.width - self.screen_region[2]).max(0.0);
+        let (left, right) = (x_range.0.min(max_x), x_range.1.min(max_x));
+        self.screen_region[1] = size.height - self.screen_region[3];
+        self.screen_region[0] = rng.gen_range(left as i32..=right as i32) as f32;
+        // teleporting shouldn't be smeared across the screen by interpolation
+        self.prev_screen_region = self.screen_region;
+    }
+    // Stop at (or wrap around) the sides of a world this size, and the top and bottom
+    pub fn keep_in(&mut self, size: WorldSize) {
+        let drawn = Rect::from_region(self.screen_region);
+        let dx = match self.edges {
+            Edges::Clamp => clamp_shift(drawn.left(), drawn.right(), size.width),
+            Edges::Wrap => {
+                let dx = wrap_shift(drawn.center().0, size.width);
+                // coming in the other side shouldn't be smeared across the screen by interpolation
+                self.prev_screen_region[0] += dx;
+                dx
+            }
+        };
+        self.screen_region[0] += dx;
+        self.screen_region[1] += clamp_shift(drawn.bottom(), drawn.top(), size.height);
+    }
+    // Where the hitbox is on screen right now, whichever way we're facing
+    pub fn hitbox_rect(&self) -> Rect {
+        self.hitbox.within(self.screen_region, self.flip_x)
+    }
+    // call at the start of every step so we know where we came from
+    pub fn store_prev(&mut self) {
+        self.prev_screen_region = self.screen_region;
+    }
+    // alpha is how far we are between the last step (0.0) and this one (1.0)
+    pub fn interpolated_region(&self, alpha: f32) -> [f32; 4] {
+        let mut region = self.screen_region;
+        for (r, prev) in region.iter_mut().zip(self.prev_screen_region) {
+            *r = prev + (*r - prev) * alpha;
         }
+        region
     }
-    pub fn move_down(&mut self) {
-        self.screen_region[1] -= self.speed;
+}
 
-        if self.screen_region[1] <= 0.0 {
-            self.screen_region[1] = 768.0;
-            self.screen_region[0] = rand::thread_rng().gen_range(0..1025) as f32;
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn animation() -> Animation {
+        Animation { states: vec![[0.0, 0.0, 1.0, 1.0]], frame_counter: 0, rate: 7, state_number: 0 }
     }
-    pub fn reset_y(&mut self){
-        self.screen_region[1] = 768.0;
-        self.screen_region[0] = rand::thread_rng().gen_range(0..1025) as f32;
+
+    #[test]
+    fn starting_out_facing_right_is_drawn_that_way() {
+        let mut started = Char_action::new([10.0, 20.0, 100.0, 100.0], animation(), 50.0, true, 0);
+        let mut turned = Char_action::new([10.0, 20.0, 100.0, 100.0], animation(), 50.0, false, 0);
+        turned.face_right();
+        assert_eq!(started.facing_right, turned.facing_right);
+        assert_eq!(started.flip_x, turned.flip_x);
+        // a lopsided hitbox ends up on the same side too
+        started.hitbox = Hitbox { x: 0.0, y: 0.0, w: 0.5, h: 1.0 };
+        turned.hitbox = started.hitbox;
+        assert_eq!(started.hitbox_rect(), turned.hitbox_rect());
     }
-}
\ No newline at end of file
+}
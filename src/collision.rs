@@ -0,0 +1,199 @@
+use serde::{Deserialize, Serialize};
+
+// An axis-aligned rectangle in screen coordinates, with the origin at the bottom
+// left like the shader's: (x, y) is the bottom left corner and w and h are never
+// negative.  Rect::new sorts out a rect given from any corner, so
+// Rect::from_region works for any screen_region.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+}
+
+impl Rect {
+    // Any corner and any signs; it gets sorted out so w and h come out positive
+    pub fn new(x: f32, y: f32, w: f32, h: f32) -> Rect {
+        Rect {
+            x: x.min(x + w),
+            y: y.min(y + h),
+            w: w.abs(),
+            h: h.abs(),
+        }
+    }
+
+    // The area a sprite's screen_region ([x, y, w, h]) actually covers when drawn
+    pub fn from_region(region: [f32; 4]) -> Rect {
+        let [x, y, w, h] = region;
+        Rect::new(x, y, w, h)
+    }
+
+    pub fn left(&self) -> f32 {
+        self.x
+    }
+
+    pub fn right(&self) -> f32 {
+        self.x + self.w
+    }
+
+    pub fn bottom(&self) -> f32 {
+        self.y
+    }
+
+    pub fn top(&self) -> f32 {
+        self.y + self.h
+    }
+
+    pub fn center(&self) -> (f32, f32) {
+        (self.x + self.w / 2.0, self.y + self.h / 2.0)
+    }
+
+    // Do the two share any area?  Rects that only touch along an edge don't count.
+    pub fn overlaps(&self, other: &Rect) -> bool {
+        self.left() < other.right()
+            && other.left() < self.right()
+            && self.bottom() < other.top()
+            && other.bottom() < self.top()
+    }
+
+    // Is all of other inside this one?  Edges are allowed to line up.
+    pub fn contains(&self, other: &Rect) -> bool {
+        self.left() <= other.left()
+            && other.right() <= self.right()
+            && self.bottom() <= other.bottom()
+            && other.top() <= self.top()
+    }
+
+    pub fn contains_point(&self, x: f32, y: f32) -> bool {
+        self.left() <= x && x <= self.right() && self.bottom() <= y && y <= self.top()
+    }
+}
+
+// The part of a sprite that counts for collisions, as fractions of its drawn
+// rect: (0, 0, 1, 1) is the whole thing.  It's given for the sprite as it sits
+// on the sheet, and mirrors along with the sprite when it's drawn flipped.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Hitbox {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+}
+
+impl Hitbox {
+    pub const FULL: Hitbox = Hitbox { x: 0.0, y: 0.0, w: 1.0, h: 1.0 };
+
+    // Where this hitbox ends up for a sprite drawn at region, mirrored left to
+    // right if flip_x is set
+    pub fn within(&self, region: [f32; 4], flip_x: bool) -> Rect {
+        let drawn = Rect::from_region(region);
+        let x = if flip_x { 1.0 - self.x - self.w } else { self.x };
+        Rect {
+            x: drawn.x + x * drawn.w,
+            y: drawn.y + self.y * drawn.h,
+            w: self.w * drawn.w,
+            h: self.h * drawn.h,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    // Whole numbers keep the float math exact, so either corner or either facing can be compared with ==
+    fn region() -> impl Strategy<Value = [f32; 4]> {
+        (-500_i16..500, -500_i16..500, 1_i16..200, 1_i16..200)
+            .prop_map(|(x, y, w, h)| [x as f32, y as f32, w as f32, h as f32])
+    }
+
+    // The same spot on screen, given from the bottom right corner
+    fn flipped(region: [f32; 4]) -> [f32; 4] {
+        let [x, y, w, h] = region;
+        [x + w, y, -w, h]
+    }
+
+    fn hitbox() -> impl Strategy<Value = Hitbox> {
+        (0_u8..=8, 0_u8..=8, 0_u8..=8, 0_u8..=8).prop_map(|(x, y, w, h)| {
+            // eighths, and never poking out past the far edge
+            let (x, y) = (x as f32 / 8.0, y as f32 / 8.0);
+            Hitbox { x, y, w: (w as f32 / 8.0).min(1.0 - x), h: (h as f32 / 8.0).min(1.0 - y) }
+        })
+    }
+
+    #[test]
+    fn flipped_regions_cover_the_same_area() {
+        // given from x = 40 back to x = -60
+        assert_eq!(Rect::from_region([40.0, 32.0, -100.0, 80.0]), Rect { x: -60.0, y: 32.0, w: 100.0, h: 80.0 });
+    }
+
+    #[test]
+    fn hitboxes_mirror_with_the_sprite() {
+        let hitbox = Hitbox { x: 0.0, y: 0.0, w: 0.25, h: 0.5 };
+        assert_eq!(hitbox.within([0.0, 0.0, 100.0, 100.0], false), Rect { x: 0.0, y: 0.0, w: 25.0, h: 50.0 });
+        assert_eq!(hitbox.within([0.0, 0.0, 100.0, 100.0], true), Rect { x: 75.0, y: 0.0, w: 25.0, h: 50.0 });
+    }
+
+    proptest! {
+        #[test]
+        fn flipping_does_not_move_the_rect(r in region()) {
+            prop_assert_eq!(Rect::from_region(flipped(r)), Rect::from_region(r));
+        }
+
+        #[test]
+        fn overlap_is_symmetric(a in region(), b in region(), flip_a: bool, flip_b: bool) {
+            let a = Rect::from_region(if flip_a { flipped(a) } else { a });
+            let b = Rect::from_region(if flip_b { flipped(b) } else { b });
+            prop_assert_eq!(a.overlaps(&b), b.overlaps(&a));
+        }
+
+        #[test]
+        fn overlap_ignores_flipping(a in region(), b in region()) {
+            let (a_rect, b_rect) = (Rect::from_region(a), Rect::from_region(b));
+            let overlap = a_rect.overlaps(&b_rect);
+            prop_assert_eq!(Rect::from_region(flipped(a)).overlaps(&b_rect), overlap);
+            prop_assert_eq!(a_rect.overlaps(&Rect::from_region(flipped(b))), overlap);
+        }
+
+        #[test]
+        fn overlap_means_some_point_is_in_both(a in region(), b in region()) {
+            let (a, b) = (Rect::from_region(a), Rect::from_region(b));
+            // the middle of the shared area, if there is one
+            let x = (a.left().max(b.left()) + a.right().min(b.right())) / 2.0;
+            let y = (a.bottom().max(b.bottom()) + a.top().min(b.top())) / 2.0;
+            if a.overlaps(&b) {
+                prop_assert!(a.contains_point(x, y) && b.contains_point(x, y));
+            }
+        }
+
+        #[test]
+        fn containing_means_overlapping(a in region(), b in region()) {
+            let (a, b) = (Rect::from_region(a), Rect::from_region(b));
+            prop_assert!(a.contains(&a) && a.overlaps(&a));
+            if a.contains(&b) {
+                prop_assert!(a.overlaps(&b));
+                prop_assert!(!b.contains(&a) || a == b);
+            }
+        }
+
+        #[test]
+        fn hitboxes_stay_inside_the_sprite(r in region(), hitbox in hitbox(), flip: bool) {
+            prop_assert!(Rect::from_region(r).contains(&hitbox.within(r, flip)));
+        }
+
+        #[test]
+        fn flipped_hitboxes_are_mirror_images(r in region(), hitbox in hitbox()) {
+            let drawn = Rect::from_region(r);
+            let (unflipped, mirrored) = (hitbox.within(r, false), hitbox.within(r, true));
+            prop_assert_eq!(mirrored.left() - drawn.left(), drawn.right() - unflipped.right());
+            prop_assert_eq!((mirrored.y, mirrored.w, mirrored.h), (unflipped.y, unflipped.w, unflipped.h));
+        }
+
+        #[test]
+        fn full_hitbox_is_the_drawn_rect(r in region(), flip: bool) {
+            prop_assert_eq!(Hitbox::FULL.within(r, flip), Rect::from_region(r));
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use std::fmt;
+use std::path::Path;
+
+use serde::Deserialize;
+
+// The tiers that ship with the game.  content/difficulty.ron is read again at
+// startup, so it can be tuned without recompiling; this copy is the fallback
+// (and what headless runs and tests use).
+const BUILT_IN: &str = include_str!("../content/difficulty.ron");
+
+// What moves the player up the difficulty tiers
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum Measure {
+    Score,
+    Seconds,
+}
+
+// Everything about the falling objects that gets harder as the game goes on
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Tier {
+    // this tier applies once the measure reaches this
+    pub from: f32,
+    // pixels per second
+    pub fall_speed: f32,
+    // seconds between spawns
+    pub spawn_interval: f32,
+    pub max_acorns: usize,
+    // 0.0 to 1.0, per spawn
+    pub hazard_chance: f32,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Difficulty {
+    pub by: Measure,
+    pub tiers: Vec<Tier>,
+}
+
+#[derive(Debug)]
+pub enum DifficultyError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    Invalid(String),
+}
+
+impl fmt::Display for DifficultyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DifficultyError::Io(e) => write!(f, "couldn't read difficulty file: {}", e),
+            DifficultyError::Parse(e) => write!(f, "couldn't parse difficulty file: {}", e),
+            DifficultyError::Invalid(msg) => write!(f, "bad difficulty file: {}", msg),
+        }
+    }
+}
+
+impl Default for Difficulty {
+    fn default() -> Self {
+        Difficulty::from_ron(BUILT_IN).expect("built-in difficulty.ron is broken")
+    }
+}
+
+impl Difficulty {
+    pub fn load(path: impl AsRef<Path>) -> Result<Difficulty, DifficultyError> {
+        let text = std::fs::read_to_string(path).map_err(DifficultyError::Io)?;
+        Difficulty::from_ron(&text)
+    }
+
+    pub fn from_ron(text: &str) -> Result<Difficulty, DifficultyError> {
+        let difficulty: Difficulty = ron::from_str(text).map_err(DifficultyError::Parse)?;
+        difficulty.validate()?;
+        Ok(difficulty)
+    }
+
+    fn validate(&self) -> Result<(), DifficultyError> {
+        let invalid = |msg: String| Err(DifficultyError::Invalid(msg));
+        match self.tiers.first() {
+            None => return invalid("there has to be at least one tier".to_string()),
+            Some(first) if first.from != 0.0 => {
+                return invalid(format!("tiers[0].from is {}, but the first tier has to start at 0", first.from))
+            }
+            _ => {}
+        }
+        for (i, tier) in self.tiers.iter().enumerate() {
+            if i > 0 && tier.from <= self.tiers[i - 1].from {
+                return invalid(format!("tiers[{}].from has to be bigger than the tier before it", i));
+            }
+            if tier.fall_speed <= 0.0 {
+                return invalid(format!("tiers[{}].fall_speed has to be positive", i));
+            }
+            if tier.spawn_interval <= 0.0 {
+                return invalid(format!("tiers[{}].spawn_interval has to be positive", i));
+            }
+            if tier.max_acorns == 0 {
+                return invalid(format!("tiers[{}].max_acorns has to be at least 1", i));
+            }
+            if !(0.0..=1.0).contains(&tier.hazard_chance) {
+                return invalid(format!("tiers[{}].hazard_chance has to be between 0 and 1", i));
+            }
+        }
+        Ok(())
+    }
+
+    // The tier in effect for a run with this score after this many seconds
+    pub fn tier_at(&self, score: usize, seconds: f32) -> &Tier {
+        &self.tiers[self.tier_index_at(score, seconds)]
+    }
+
+    pub fn tier_index_at(&self, score: usize, seconds: f32) -> usize {
+        let progress = match self.by {
+            Measure::Score => score as f32,
+            Measure::Seconds => seconds,
+        };
+        // tiers are sorted, so the last one we've reached wins
+        self.tiers
+            .iter()
+            .rposition(|tier| progress >= tier.from)
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TWO_TIERS: &str = "(
+        by: Score,
+        tiers: [
+            (from: 0.0, fall_speed: 100.0, spawn_interval: 2.0, max_acorns: 2, hazard_chance: 0.0),
+            (from: 10.0, fall_speed: 200.0, spawn_interval: 1.0, max_acorns: 4, hazard_chance: 0.5),
+        ],
+    )";
+
+    #[test]
+    fn score_picks_the_tier() {
+        let difficulty = Difficulty::from_ron(TWO_TIERS).unwrap();
+        assert_eq!(difficulty.tier_at(9, 1000.0).fall_speed, 100.0);
+        let tier = difficulty.tier_at(10, 0.0);
+        assert_eq!(
+            *tier,
+            Tier { from: 10.0, fall_speed: 200.0, spawn_interval: 1.0, max_acorns: 4, hazard_chance: 0.5 }
+        );
+        assert_eq!(difficulty.tier_at(500, 0.0), tier);
+    }
+
+    #[test]
+    fn seconds_pick_the_tier_too() {
+        let difficulty = Difficulty::from_ron(&TWO_TIERS.replace("Score", "Seconds")).unwrap();
+        assert_eq!(difficulty.tier_at(500, 9.5).max_acorns, 2);
+        assert_eq!(difficulty.tier_at(0, 10.0).max_acorns, 4);
+    }
+
+    #[test]
+    fn built_in_file_is_valid() {
+        let difficulty = Difficulty::default();
+        assert_eq!(difficulty.by, Measure::Score);
+        assert_eq!(difficulty.tier_at(0, 0.0).fall_speed, 120.0);
+    }
+
+    #[test]
+    fn bad_files_are_rejected() {
+        let unsorted = TWO_TIERS.replace("from: 10.0", "from: 0.0");
+        assert!(matches!(Difficulty::from_ron(&unsorted), Err(DifficultyError::Invalid(_))));
+        let chance = TWO_TIERS.replace("hazard_chance: 0.5", "hazard_chance: 1.5");
+        assert!(matches!(Difficulty::from_ron(&chance), Err(DifficultyError::Invalid(_))));
+        assert!(matches!(Difficulty::from_ron("(by: Score)"), Err(DifficultyError::Parse(_))));
+    }
+}
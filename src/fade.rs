@@ -0,0 +1,38 @@
+use crate::char_action::Char_action;
+
+// how long something that got picked up takes to fade away, in seconds
+pub const FADE_SECONDS: f32 = 0.3;
+// how much bigger it gets while it fades, per second (1.0 would double it)
+const FADE_GROWTH: f32 = 1.5;
+
+// Something the squirrel just picked up, left behind for a moment so it can
+// fade out instead of vanishing.  It's only for show: nothing collides with it.
+#[derive(Clone)]
+pub struct Fade {
+    pub body: Char_action,
+    // seconds since it was picked up
+    pub age: f32,
+}
+
+impl Fade {
+    pub fn new(mut body: Char_action) -> Fade {
+        // it's not going anywhere, so there's nothing to interpolate from
+        body.store_prev();
+        Fade { body, age: 0.0 }
+    }
+
+    // 1.0 when it's picked up, fading to 0.0 as it goes away
+    pub fn opacity(&self) -> f32 {
+        (1.0 - self.age / FADE_SECONDS).clamp(0.0, 1.0)
+    }
+}
+
+// Grow and fade everything a bit, and drop the ones that are gone
+pub fn update(fades: &mut Vec<Fade>, dt: f32) {
+    fades.retain_mut(|fade| {
+        fade.age += dt;
+        fade.body.scale = 1.0 + FADE_GROWTH * fade.age;
+        fade.body.tint[3] = fade.opacity();
+        fade.age < FADE_SECONDS
+    });
+}
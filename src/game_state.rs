@@ -1,14 +1,63 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::powerup::PowerUps;
+use crate::scene::Scene;
+
+// how many acorns you can drop (or pinecones you can take) before the game ends
+pub const START_LIVES: u32 = 3;
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct GameState{
-    pub chars_typed: u32,
+    // the name being typed in for the high score table
+    pub chars_typed: String,
     pub score: usize,
-    pub score_changing: bool,
+    // catches in a row without dropping an acorn or getting hit
+    pub combo: u32,
+    pub lives: u32,
+    // seconds left until the squirrel can move again after being hit
+    pub stunned: f32,
+    pub power_ups: PowerUps,
+    // seconds spent in the current run
+    pub elapsed: f32,
+    // which difficulty tier the run has reached
+    pub tier: usize,
+    // which of the level's waves the run is on, if there's a level
+    pub wave: usize,
+    // seconds since the wave started
+    pub wave_time: f32,
+    // acorns caught since the wave started
+    pub wave_caught: u32,
+    // the score when the wave started
+    pub wave_start_score: usize,
+    // the run ended by getting through the last wave
+    pub cleared: bool,
+    pub scene: Scene,
+    // the seed this run started from, so it can be reproduced
+    pub seed: u64,
+    // all randomness in the game comes from here
+    pub rng: ChaCha8Rng,
 }
 
-pub fn init_game_state() -> GameState {
+pub fn init_game_state(seed: u64) -> GameState {
     // any necessary functions
     GameState {
-        chars_typed : 0,
+        chars_typed : String::new(),
         score : 0,
-        score_changing : false,
+        combo : 0,
+        lives : START_LIVES,
+        stunned : 0.0,
+        power_ups : PowerUps::default(),
+        elapsed : 0.0,
+        tier : 0,
+        wave : 0,
+        wave_time : 0.0,
+        wave_caught : 0,
+        wave_start_score : 0,
+        cleared : false,
+        scene : Scene::Title,
+        seed,
+        rng : ChaCha8Rng::seed_from_u64(seed),
     }
 }
\ No newline at end of file
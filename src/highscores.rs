@@ -0,0 +1,212 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+// how many scores the table keeps
+pub const MAX_ENTRIES: usize = 10;
+// and how long a name can be
+pub const MAX_NAME_LEN: usize = 12;
+
+// Bump this whenever Entry changes shape, so old files get set aside instead of misread
+const VERSION: u32 = 1;
+
+// One finished run that made the table
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub name: String,
+    pub score: usize,
+    // the day it was played, as YYYY-MM-DD
+    pub date: String,
+    // so the run can be played again with --seed
+    pub seed: u64,
+    // how long the run lasted
+    pub seconds: f32,
+}
+
+// What's actually in the file
+#[derive(Serialize, Deserialize)]
+struct ScoreFile {
+    version: u32,
+    entries: Vec<Entry>,
+}
+
+// Just enough of a ScoreFile to check the version before reading the rest
+#[derive(Deserialize)]
+struct VersionOnly {
+    version: u32,
+}
+
+#[derive(Debug)]
+pub enum HighScoreError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    Write(ron::Error),
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for HighScoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HighScoreError::Io(e) => write!(f, "couldn't read or write high scores: {}", e),
+            HighScoreError::Parse(e) => write!(f, "couldn't parse high scores: {}", e),
+            HighScoreError::Write(e) => write!(f, "couldn't write high scores: {}", e),
+            HighScoreError::UnsupportedVersion(v) => {
+                write!(f, "high score file version {} is not supported (expected {})", v, VERSION)
+            }
+        }
+    }
+}
+
+impl From<std::io::Error> for HighScoreError {
+    fn from(e: std::io::Error) -> Self {
+        HighScoreError::Io(e)
+    }
+}
+
+// The best scores so far, highest first
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HighScores {
+    pub entries: Vec<Entry>,
+}
+
+impl HighScores {
+    // Where the table lives: e.g. ~/.local/share/nut-wars/highscores.ron on Linux.
+    // None if the OS doesn't have a data directory for us.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("nut-wars").join("highscores.ron"))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<HighScores, HighScoreError> {
+        let text = std::fs::read_to_string(path)?;
+        HighScores::from_ron(&text)
+    }
+
+    // Never fails: a missing file is just an empty table, and a broken one gets
+    // moved aside (so saving doesn't clobber it) with a warning.
+    pub fn load_or_empty(path: impl AsRef<Path>) -> HighScores {
+        let path = path.as_ref();
+        match HighScores::load(path) {
+            Ok(scores) => scores,
+            Err(HighScoreError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => HighScores::default(),
+            Err(e) => {
+                let backup = path.with_extension("ron.bad");
+                log::warn!("{}; moving it to {} and starting a new table", e, backup.display());
+                if let Err(e) = std::fs::rename(path, &backup) {
+                    log::warn!("couldn't move {}: {}", path.display(), e);
+                }
+                HighScores::default()
+            }
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), HighScoreError> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, self.to_ron()?)?;
+        Ok(())
+    }
+
+    pub fn from_ron(text: &str) -> Result<HighScores, HighScoreError> {
+        let VersionOnly { version } = ron::from_str(text).map_err(HighScoreError::Parse)?;
+        if version != VERSION {
+            return Err(HighScoreError::UnsupportedVersion(version));
+        }
+        let file: ScoreFile = ron::from_str(text).map_err(HighScoreError::Parse)?;
+        let mut scores = HighScores { entries: file.entries };
+        // don't trust the file to be sorted or the right length
+        scores.entries.sort_by_key(|e| std::cmp::Reverse(e.score));
+        scores.entries.truncate(MAX_ENTRIES);
+        Ok(scores)
+    }
+
+    pub fn to_ron(&self) -> Result<String, HighScoreError> {
+        let file = ScoreFile {
+            version: VERSION,
+            entries: self.entries.clone(),
+        };
+        ron::ser::to_string_pretty(&file, ron::ser::PrettyConfig::default()).map_err(HighScoreError::Write)
+    }
+
+    // Would this score get onto the table?
+    pub fn qualifies(&self, score: usize) -> bool {
+        score > 0 && (self.entries.len() < MAX_ENTRIES || self.entries.iter().any(|e| score > e.score))
+    }
+
+    // Put an entry in its place and return where that is (0 is the top), or
+    // None if it didn't make the cut.  Ties go below the scores already there.
+    pub fn insert(&mut self, entry: Entry) -> Option<usize> {
+        if !self.qualifies(entry.score) {
+            return None;
+        }
+        let rank = self
+            .entries
+            .iter()
+            .position(|e| entry.score > e.score)
+            .unwrap_or(self.entries.len());
+        self.entries.insert(rank, entry);
+        self.entries.truncate(MAX_ENTRIES);
+        Some(rank)
+    }
+}
+
+// Today's date for a new entry
+pub fn today() -> String {
+    chrono::Local::now().format("%Y-%m-%d").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, score: usize) -> Entry {
+        Entry { name: name.to_string(), score, date: "2024-01-01".to_string(), seed: 7, seconds: 61.5 }
+    }
+
+    #[test]
+    fn keeps_the_best_in_order() {
+        let mut scores = HighScores::default();
+        for i in 0..MAX_ENTRIES {
+            assert_eq!(scores.insert(entry("filler", 10 + i)), Some(0));
+        }
+        assert!(!scores.qualifies(10));
+        assert_eq!(scores.insert(entry("low", 5)), None);
+        assert_eq!(scores.insert(entry("tie", 15)), Some(5));
+        assert_eq!(scores.insert(entry("best", 100)), Some(0));
+        assert_eq!(scores.entries.len(), MAX_ENTRIES);
+        assert_eq!(scores.entries.last().unwrap().score, 12);
+        assert!(!scores.qualifies(0));
+    }
+
+    #[test]
+    fn round_trips_through_a_file() {
+        let mut scores = HighScores::default();
+        scores.insert(entry("ada", 30));
+        scores.insert(entry("bo", 12));
+        let path = std::env::temp_dir().join(format!("nut-wars-test-{}", std::process::id())).join("scores.ron");
+        scores.save(&path).unwrap();
+        assert_eq!(HighScores::load(&path).unwrap(), scores);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn bad_files_are_set_aside() {
+        let dir = std::env::temp_dir().join(format!("nut-wars-bad-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("scores.ron");
+
+        assert_eq!(HighScores::load_or_empty(&path), HighScores::default());
+
+        std::fs::write(&path, "(version: 1, entries: [oops").unwrap();
+        assert!(matches!(HighScores::load(&path), Err(HighScoreError::Parse(_))));
+        assert_eq!(HighScores::load_or_empty(&path), HighScores::default());
+        assert!(!path.exists());
+        assert!(dir.join("scores.ron.bad").exists());
+
+        let future = "(version: 99, entries: [], something_new: true)";
+        assert!(matches!(HighScores::from_ron(future), Err(HighScoreError::UnsupportedVersion(99))));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
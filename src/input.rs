@@ -2,6 +2,47 @@ pub use winit::dpi::PhysicalPosition as MousePos;
 pub use winit::event::VirtualKeyCode as Key;
 use winit::event::{ElementState, MouseButton};
 
+// The part of the input state the simulation actually reads.  It's a plain value
+// so the World can be stepped without any winit events around.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct InputFrame {
+    pub left: bool,
+    pub right: bool,
+    // held, not just pressed, so letting go early can cut the jump short
+    pub jump: bool,
+    // these two are only true on the step the key went down
+    pub pause: bool,
+    pub start: bool,
+}
+
+impl InputFrame {
+    // Pack into one byte per frame for replay files
+    pub fn to_bits(self) -> u8 {
+        (self.left as u8)
+            | ((self.right as u8) << 1)
+            | ((self.pause as u8) << 2)
+            | ((self.start as u8) << 3)
+            | ((self.jump as u8) << 4)
+    }
+    pub fn from_bits(bits: u8) -> InputFrame {
+        InputFrame {
+            left: bits & 1 != 0,
+            right: bits & (1 << 1) != 0,
+            pause: bits & (1 << 2) != 0,
+            start: bits & (1 << 3) != 0,
+            jump: bits & (1 << 4) != 0,
+        }
+    }
+    // The same frame with key presses used up, for when one render frame runs several steps
+    pub fn held(self) -> InputFrame {
+        InputFrame {
+            pause: false,
+            start: false,
+            ..self
+        }
+    }
+}
+
 pub struct Input {
     now_keys: Box<[bool]>,
     prev_keys: Box<[bool]>,
@@ -71,6 +112,15 @@ impl Input {
         (if self.is_key_down(down) { -1.0 } else { 0.0 })
             + (if self.is_key_down(up) { 1.0 } else { 0.0 })
     }
+    pub fn frame(&self) -> InputFrame {
+        InputFrame {
+            left: self.is_key_down(Key::Left),
+            right: self.is_key_down(Key::Right),
+            jump: self.is_key_down(Key::Space) || self.is_key_down(Key::Up),
+            pause: self.is_key_pressed(Key::Escape) || self.is_key_pressed(Key::P),
+            start: self.is_key_pressed(Key::Return),
+        }
+    }
     pub fn next_frame(&mut self) {
         self.prev_keys.copy_from_slice(&self.now_keys);
         self.prev_mouse.copy_from_slice(&self.now_mouse);
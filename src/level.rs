@@ -0,0 +1,340 @@
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
+
+use serde::Deserialize;
+
+// A level: a map to play on and a list of waves to get through, one after the
+// other.  Without one the game just keeps going, getting harder by the
+// difficulty tiers, until the squirrel runs out of lives.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Level {
+    pub name: String,
+    // a tilemap CSV, relative to the level file; the built-in forest if left out
+    #[serde(default)]
+    pub map: Option<String>,
+    pub waves: Vec<Wave>,
+}
+
+// What has to happen to finish a wave
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub enum Goal {
+    // catch this many acorns during the wave
+    Catch(u32),
+    // earn this many points during the wave
+    Score(usize),
+    // still be going when the wave's time is up
+    Survive,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Wave {
+    // shown when the wave starts
+    pub name: String,
+    // pixels per second
+    pub fall_speed: f32,
+    // seconds between spawns
+    pub spawn_interval: f32,
+    // how many things can be falling at once
+    pub max_live: usize,
+    // 0.0 to 1.0, how likely each spawn is a hazard or a power-up; the rest are acorns
+    #[serde(default)]
+    pub hazard_chance: f32,
+    #[serde(default)]
+    pub power_up_chance: f32,
+    // the left and right edges of where things start falling from
+    #[serde(default = "full_width")]
+    pub x: (f32, f32),
+    // how long there is to meet the goal; no limit if it's left out
+    #[serde(default)]
+    pub seconds: Option<f32>,
+    pub goal: Goal,
+}
+
+// as far right as things fit in the world
+fn full_width() -> (f32, f32) {
+    (0.0, f32::MAX)
+}
+
+#[derive(Debug)]
+pub enum LevelError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    // field is the path to it, like "waves[2].fall_speed"; line is None if the
+    // field was left out of the file
+    Invalid { line: Option<usize>, field: String, msg: String },
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelError::Io(e) => write!(f, "couldn't read level file: {}", e),
+            LevelError::Parse(e) => write!(f, "couldn't parse level file: {}", e),
+            LevelError::Invalid { line: Some(line), field, msg } => {
+                write!(f, "bad level file, line {}: {} {}", line, field, msg)
+            }
+            LevelError::Invalid { line: None, field, msg } => write!(f, "bad level file: {} {}", field, msg),
+        }
+    }
+}
+
+impl From<std::io::Error> for LevelError {
+    fn from(e: std::io::Error) -> Self {
+        LevelError::Io(e)
+    }
+}
+
+impl Level {
+    pub fn load(path: impl AsRef<Path>) -> Result<Level, LevelError> {
+        let text = std::fs::read_to_string(path)?;
+        Level::from_ron(&text)
+    }
+
+    pub fn from_ron(text: &str) -> Result<Level, LevelError> {
+        let level: Level = ron::from_str(text).map_err(LevelError::Parse)?;
+        // serde doesn't keep track of where things were, so if something's wrong
+        // go back to the text to find the line it's on
+        level.validate().map_err(|(wave, field, msg)| {
+            let code = blank_comments_and_strings(text);
+            let span = match wave {
+                Some(i) => wave_spans(&code).get(i).cloned(),
+                None => Some(0..code.len()),
+            };
+            let line = span.and_then(|span| find_field(&code, span, field)).map(|at| line_of(text, at));
+            let field = match wave {
+                Some(i) => format!("waves[{}].{}", i, field),
+                None => field.to_string(),
+            };
+            LevelError::Invalid { line, field, msg }
+        })?;
+        Ok(level)
+    }
+
+    // On failure: which wave (if it's in one), which field, and what's wrong with it
+    fn validate(&self) -> Result<(), (Option<usize>, &'static str, String)> {
+        if self.waves.is_empty() {
+            return Err((None, "waves", "has to have at least one wave in it".to_string()));
+        }
+        for (i, wave) in self.waves.iter().enumerate() {
+            let invalid = |field, msg: &str| Err((Some(i), field, msg.to_string()));
+            if wave.fall_speed <= 0.0 {
+                return invalid("fall_speed", "has to be positive");
+            }
+            if wave.spawn_interval <= 0.0 {
+                return invalid("spawn_interval", "has to be positive");
+            }
+            if wave.max_live == 0 {
+                return invalid("max_live", "has to be at least 1");
+            }
+            if !(0.0..=1.0).contains(&wave.hazard_chance) {
+                return invalid("hazard_chance", "has to be between 0 and 1");
+            }
+            if !(0.0..=1.0).contains(&wave.power_up_chance) {
+                return invalid("power_up_chance", "has to be between 0 and 1");
+            }
+            if wave.hazard_chance + wave.power_up_chance > 1.0 {
+                return invalid("power_up_chance", "plus hazard_chance can't be more than 1");
+            }
+            if wave.x.0 < 0.0 || wave.x.0 > wave.x.1 {
+                return invalid("x", "has to be (left, right) with 0 <= left <= right");
+            }
+            if wave.seconds.is_some_and(|s| s <= 0.0) {
+                return invalid("seconds", "has to be positive");
+            }
+            match wave.goal {
+                Goal::Catch(0) | Goal::Score(0) => return invalid("goal", "has to ask for at least 1"),
+                Goal::Survive if wave.seconds.is_none() => {
+                    return invalid("goal", "is Survive, so the wave needs seconds to say for how long")
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+// The same text with comments and the insides of strings replaced by spaces, so
+// the bracket counting below can't be thrown off by them.  Everything stays at
+// the same offset.
+fn blank_comments_and_strings(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    let blank = |c: char| if c == '\n' { '\n' } else { ' ' };
+    while let Some(c) = chars.next() {
+        match c {
+            '/' if chars.peek() == Some(&'/') => {
+                out.push(' ');
+                while let Some(&c) = chars.peek() {
+                    if c == '\n' {
+                        break;
+                    }
+                    out.extend(std::iter::repeat_n(' ', c.len_utf8()));
+                    chars.next();
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                out.push(' ');
+                let mut last = ' ';
+                for c in chars.by_ref() {
+                    out.extend(std::iter::repeat_n(blank(c), c.len_utf8()));
+                    if last == '*' && c == '/' {
+                        break;
+                    }
+                    last = c;
+                }
+            }
+            '"' => {
+                out.push('"');
+                let mut escaped = false;
+                for c in chars.by_ref() {
+                    if c == '"' && !escaped {
+                        out.push('"');
+                        break;
+                    }
+                    escaped = c == '\\' && !escaped;
+                    out.extend(std::iter::repeat_n(blank(c), c.len_utf8()));
+                }
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// Where each entry of the waves list starts and ends
+fn wave_spans(code: &str) -> Vec<Range<usize>> {
+    let mut spans = vec![];
+    let Some(waves) = find_field(code, 0..code.len(), "waves") else {
+        return spans;
+    };
+    let Some(open) = code[waves..].find('[').map(|at| waves + at) else {
+        return spans;
+    };
+    let mut depth = 0;
+    let mut entry_start = None;
+    for (at, c) in code[open..].char_indices() {
+        let at = open + at;
+        match c {
+            '[' | '(' | '{' => {
+                if depth == 1 && entry_start.is_none() {
+                    entry_start = Some(at);
+                }
+                depth += 1;
+            }
+            ']' | ')' | '}' => {
+                depth -= 1;
+                if depth == 1 {
+                    if let Some(start) = entry_start.take() {
+                        spans.push(start..at + 1);
+                    }
+                } else if depth == 0 {
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+    spans
+}
+
+// The offset of `field:` within span, if it's there
+fn find_field(code: &str, span: Range<usize>, field: &str) -> Option<usize> {
+    let part = &code[span.clone()];
+    let mut from = 0;
+    while let Some(at) = part[from..].find(field).map(|at| from + at) {
+        let before = part[..at].chars().next_back();
+        let after = part[at + field.len()..].trim_start();
+        let whole_word = !before.is_some_and(|c| c.is_alphanumeric() || c == '_');
+        if whole_word && after.starts_with(':') {
+            return Some(span.start + at);
+        }
+        from = at + field.len();
+    }
+    None
+}
+
+fn line_of(text: &str, offset: usize) -> usize {
+    text[..offset].matches('\n').count() + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TWO_WAVES: &str = r#"// "waves: [" in a comment doesn't count
+(
+    name: "Test (two waves)",
+    waves: [
+        (
+            name: "One",
+            fall_speed: 100.0,
+            spawn_interval: 1.0,
+            max_live: 3,
+            goal: Catch(5),
+        ),
+        (
+            name: "Two: \"the sequel\"",
+            fall_speed: 200.0,
+            spawn_interval: 0.5,
+            max_live: 5,
+            hazard_chance: 0.2,
+            x: (100.0, 900.0),
+            seconds: Some(30.0),
+            goal: Survive,
+        ),
+    ],
+)
+"#;
+
+    #[test]
+    fn reads_waves_with_defaults() {
+        let level = Level::from_ron(TWO_WAVES).unwrap();
+        assert_eq!(level.map, None);
+        assert_eq!(level.waves.len(), 2);
+        assert_eq!(level.waves[0].x, (0.0, f32::MAX));
+        assert_eq!(level.waves[0].seconds, None);
+        assert_eq!(level.waves[1].goal, Goal::Survive);
+        assert_eq!(level.waves[1].x, (100.0, 900.0));
+        // the one that comes with the game
+        Level::from_ron(include_str!("../content/levels/forest.ron")).unwrap();
+    }
+
+    #[test]
+    fn mistakes_point_at_the_line_and_field() {
+        let slow = TWO_WAVES.replace("fall_speed: 200.0", "fall_speed: -1.0");
+        match Level::from_ron(&slow) {
+            Err(LevelError::Invalid { line, field, .. }) => {
+                assert_eq!((line, field.as_str()), (Some(14), "waves[1].fall_speed"))
+            }
+            other => panic!("{:?}", other),
+        }
+        let forever = TWO_WAVES.replace("            seconds: Some(30.0),\n", "");
+        let err = Level::from_ron(&forever).unwrap_err();
+        assert!(matches!(&err, LevelError::Invalid { line: Some(19), .. }), "{}", err);
+        assert_eq!(
+            err.to_string(),
+            "bad level file, line 19: waves[1].goal is Survive, so the wave needs seconds to say for how long"
+        );
+        // nothing in the list, so it points at the list itself
+        let empty = Level::from_ron("(name: \"Empty\", waves: [])").unwrap_err();
+        assert!(matches!(&empty, LevelError::Invalid { line: Some(1), .. }), "{}", empty);
+        // typos are parse errors, which know their own line
+        let typo = TWO_WAVES.replace("max_live: 3", "max_lives: 3");
+        match Level::from_ron(&typo) {
+            Err(LevelError::Parse(e)) => assert_eq!(e.position.line, 9),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn finds_each_wave() {
+        let code = blank_comments_and_strings(TWO_WAVES);
+        assert_eq!(code.len(), TWO_WAVES.len());
+        let spans = wave_spans(&code);
+        assert_eq!(spans.len(), 2);
+        assert!(code[spans[0].clone()].starts_with('(') && code[spans[0].clone()].ends_with(')'));
+        assert_eq!(line_of(TWO_WAVES, spans[1].start), 12);
+    }
+}
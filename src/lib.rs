@@ -0,0 +1,27 @@
+// Everything that runs without a window: the simulation and what it loads and
+// saves.  The game binary (main.rs) puts it on screen; the tests and benchmarks
+// drive it directly.
+pub mod animation;
+pub mod bounds;
+pub mod broadphase;
+pub mod camera;
+pub mod char_action;
+pub mod collision;
+pub mod difficulty;
+pub mod fade;
+pub mod game_state;
+pub mod highscores;
+pub mod input;
+pub mod level;
+pub mod options;
+pub mod parallax;
+pub mod physics;
+pub mod popup;
+pub mod powerup;
+pub mod replay;
+pub mod savegame;
+pub mod scene;
+pub mod spawner;
+pub mod tilemap;
+pub mod timestep;
+pub mod world;
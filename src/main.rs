@@ -1,23 +1,24 @@
-use std::{borrow::Cow, f32::consts::E};
+use std::f32::consts::E;
 
 #[cfg(target_arch = "aarch64")]
 use std::arch::aarch64::float32x2_t;
-use animation::Animation;
 use wgpu::Texture;
 use winit::{
     event::{Event, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
     window::Window,
 };
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use imageproc::rect::Rect;
 use rusttype::{Font, Scale};
-mod game_state;
-mod char_action;
 mod gpus;
-mod input;
-mod animation;
+mod sprite_game;
+use interactive_drawing::{
+    camera, char_action, collision, difficulty, highscores, input, level, options, parallax, replay, savegame,
+    scene, tilemap, timestep, world,
+};
 use rand::Rng;
+use sprite_game::{Blend, GPUBackground, GPUCamera, GPUSprite, Layer, FLIP_X};
 use bytemuck::{Pod, Zeroable};
 use glyphon::{
     Attrs, Buffer, Color, Family, FontSystem, Metrics, Resolution, Shaping, SwashCache, TextArea,
@@ -27,28 +28,59 @@ use wgpu::{
     CompositeAlphaMode, MultisampleState, 
 };
 
-#[repr(C)]
-#[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
-struct GPUSprite {
-    screen_region: [f32;4],
-    // Textures with a bunch of sprites are often called "sprite sheets"
-    sheet_region: [f32;4]
-}
-
 // In WGPU, we define an async function whose operation can be suspended and resumed.
 // This is because on web, we can't take over the main event loop and must leave it to
 // the browser.  On desktop, we'll just be running this function to completion.
-async fn run(event_loop: EventLoop<()>, window: Window) {
+async fn run(event_loop: EventLoop<()>, window: Window, opts: options::Options, loaded: Loaded) {
     let mut gpu = gpus::WGPU::new(&window).await;
-    let mut gs = game_state::init_game_state();
+    // Everything headless was already loaded (and checked) by main
+    let (mut world, mut playback, level_path) = loaded;
+    // Replays shouldn't end up on the high score table.  Recordings start from an
+    // empty one too, since whether a score makes the table decides which screen
+    // comes next, and playback has to go the same way.
+    let high_score_path =
+        if playback.is_none() && opts.record.is_none() { highscores::HighScores::default_path() } else { None };
+    if let Some(path) = high_score_path.as_ref() {
+        world.high_scores = highscores::HighScores::load_or_empty(path);
+    }
+    let level_name = level_path.as_ref().map(|path| path.display().to_string());
+    let mut recording =
+        opts.record.as_ref().map(|_| replay::Replay::new(world.gs.seed, level_name, world.content_hash()));
+    // Pick up a run left over from last time, unless we're recording or replaying,
+    // since those have to start from nothing but the seed
+    let save_path = if playback.is_none() && recording.is_none() { savegame::SaveGame::default_path() } else { None };
+    if let Some(path) = save_path.as_ref().filter(|path| path.exists()) {
+        match savegame::SaveGame::load(path).and_then(|save| save.restore(&mut world)) {
+            Ok(()) => {
+                // come back to the pause screen rather than straight into the action
+                world.gs.scene = scene::Scene::Paused;
+                log::info!("Resumed the run saved in {}", path.display());
+            }
+            Err(e) => log::warn!("{}; starting over", e),
+        }
+    }
 
     let (squirrel_tex, mut squirrel_img) = gpus::WGPU::load_texture("content/spritesheet.png", Some("squirrel"), &gpu.device, &gpu.queue).await.expect("Couldn't load squirrel sprite sheet");
-    let view: wgpu::TextureView = squirrel_tex.create_view(&wgpu::TextureViewDescriptor::default());
-    let sampler = gpu.device.create_sampler(&wgpu::SamplerDescriptor::default());
-
-    let (tex_bg, mut img_bg) = gpus::WGPU::load_texture("content/forest_background.png", Some("background"), &gpu.device, &gpu.queue ).await.expect("Couldn't load background");
-    let view_bg = tex_bg.create_view(&wgpu::TextureViewDescriptor::default());
-    let sampler_bg = gpu.device.create_sampler(&wgpu::SamplerDescriptor::default());
+    let (tileset_tex, _) = gpus::WGPU::load_texture("content/tileset.png", Some("tileset"), &gpu.device, &gpu.queue).await.expect("Couldn't load tileset");
+    // Like the difficulty, read the background layers again so they can be changed without a rebuild
+    let parallax = match parallax::Parallax::load("content/parallax.ron") {
+        Ok(parallax) => parallax,
+        Err(e) => {
+            log::warn!("{}; using the built-in parallax layers", e);
+            parallax::Parallax::default()
+        }
+    };
+    // A layer that's missing its texture just doesn't get drawn
+    let mut parallax_textures = vec![];
+    for layer in parallax.layers {
+        let path = Path::new("content").join(&layer.texture);
+        match gpus::WGPU::load_texture(&path, Some(&layer.texture), &gpu.device, &gpu.queue).await {
+            Ok((tex, _)) => parallax_textures.push((layer, tex)),
+            Err(e) => log::warn!("Couldn't load background layer {}: {}", path.display(), e),
+        }
+    }
+    // the effects get their own copy of the sheet, since each sprite group owns its texture
+    let (effects_tex, _) = gpus::WGPU::load_texture("content/spritesheet.png", Some("effects"), &gpu.device, &gpu.queue).await.expect("Couldn't load squirrel sprite sheet");
 
     // Set up text renderer
     let mut font_system = FontSystem::new();
@@ -62,301 +94,59 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
     
     buffer.set_size(&mut font_system, physical_width, physical_height);
 
-    let score_text = format!("Score: {}", gs.score);
-    buffer.set_text(&mut font_system, &score_text, Attrs::new().family(Family::SansSerif), Shaping::Advanced);
+    // The current scene decides what text goes on screen
+    let mut shown_text = world.gs.scene.render(&world).text;
+    buffer.set_text(&mut font_system, &shown_text, Attrs::new().family(Family::SansSerif), Shaping::Advanced);
     buffer.shape_until_scroll(&mut font_system);
 
-    // Load the shaders from disk.  Remember, shader programs are things we compile for
-    // our GPU so that it can compute vertices and colorize fragments.
-    let shader = gpu.device.create_shader_module(wgpu::ShaderModuleDescriptor {
-        label: None,
-        // Cow is a "copy on write" wrapper that abstracts over owned or borrowed memory.
-        // Here we just need to use it since wgpu wants "some text" to compile a shader from.
-        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("shader.wgsl"))),
-    });
-
-    let texture_bind_group_layout =
-    gpu.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-        label: None,
-        // This bind group's first entry is for the texture and the second is for the sampler.
-        entries: &[
-            // The texture binding
-            wgpu::BindGroupLayoutEntry {
-                // This matches the binding number in the shader
-                binding: 0,
-                // Only available in the fragment shader
-                visibility: wgpu::ShaderStages::FRAGMENT,
-                // It's a texture binding
-                ty: wgpu::BindingType::Texture {
-                    // We can use it with float samplers
-                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                    // It's being used as a 2D texture
-                    view_dimension: wgpu::TextureViewDimension::D2,
-                    // This is not a multisampled texture
-                    multisampled: false,
-                },
-                // This is not an array texture, so it has None for count
-                count: None,
-            },
-            // The sampler binding
-            wgpu::BindGroupLayoutEntry {
-                // This matches the binding number in the shader
-                binding: 1,
-                // Only available in the fragment shader
-                visibility: wgpu::ShaderStages::FRAGMENT,
-                // It's a sampler
-                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                // No count
-                count: None,
-            },
-        ],
-    });
-    let sprite_bind_group_layout =
-    gpu.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-        label: None,
-        entries: &[
-            // The camera binding
-            wgpu::BindGroupLayoutEntry {
-                // This matches the binding in the shader
-                binding: 0,
-                // Available in vertex shader
-                visibility: wgpu::ShaderStages::VERTEX,
-                // It's a buffer
-                ty: wgpu::BindingType::Buffer {
-                    // Specifically, a uniform buffer
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None
-                },
-                // No count, not a buffer array binding
-                count: None,
-            },
-            // The sprite buffer binding
-            wgpu::BindGroupLayoutEntry {
-                // This matches the binding in the shader
-                binding: 1,
-                // Available in vertex shader
-                visibility: wgpu::ShaderStages::VERTEX,
-                // It's a buffer
-                ty: wgpu::BindingType::Buffer {
-                    // Specifically, a storage buffer
-                    ty: wgpu::BufferBindingType::Storage{read_only:true},
-                    has_dynamic_offset: false,
-                    min_binding_size: None
-                },
-                // No count, not a buffer array binding
-                count: None,
-            },
-        ],
-    });
-    let pipeline_layout = gpu.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-        label: None,
-        bind_group_layouts: &[&sprite_bind_group_layout, &texture_bind_group_layout],
-        push_constant_ranges: &[],
-    });
-
-    let pipeline_layout_bg = gpu.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-        label: None,
-        bind_group_layouts: &[&texture_bind_group_layout],
-        push_constant_ranges: &[],
-    });
-
-    let texture_bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
-        label: None,
-        layout: &texture_bind_group_layout,
-        entries: &[
-            // One for the texture, one for the sampler
-            wgpu::BindGroupEntry {
-                binding: 0,
-                resource: wgpu::BindingResource::TextureView(&view),
-            },
-            wgpu::BindGroupEntry {
-                binding: 1,
-                resource: wgpu::BindingResource::Sampler(&sampler),
-            },
-        ],
-    });
-
-    let tex_bg_bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
-        label: None,
-        layout: &texture_bind_group_layout,
-        entries: &[
-            // One for the texture, one for the sampler
-            wgpu::BindGroupEntry {
-                binding: 0,
-                resource: wgpu::BindingResource::TextureView(&view_bg),
-            },
-            wgpu::BindGroupEntry {
-                binding: 1,
-                resource: wgpu::BindingResource::Sampler(&sampler_bg),
-            },
-        ],
-    });
-
-    // Our specific "function" is going to be a draw call using our shaders. That's what we
-    // set up here, calling the result a render pipeline.  It's not only what shaders to use,
-    // but also how to interpret streams of vertices (e.g. as separate triangles or as a list of lines),
-    // whether to draw both the fronts and backs of triangles, and how many times to run the pipeline for
-    // things like multisampling antialiasing.
-    let render_pipeline = gpu.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-        label: None,
-        layout: Some(&pipeline_layout),
-        vertex: wgpu::VertexState {
-            module: &shader,
-            entry_point: "vs_main",
-            buffers: &[],
-        },
-        fragment: Some(wgpu::FragmentState {
-            module: &shader,
-            entry_point: "fs_main",
-            targets: &[Some(gpu.config.format.into())],
-        }),
-        primitive: wgpu::PrimitiveState::default(),
-        depth_stencil: None,
-        multisample: wgpu::MultisampleState::default(),
-        multiview: None,
-    });
-
-    let render_pipeline_bg = gpu.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-        label: None,
-        layout: Some(&pipeline_layout_bg),
-        vertex: wgpu::VertexState {
-            module: &shader,
-            entry_point: "vs_main_bg",
-            buffers: &[],
-        },
-        fragment: Some(wgpu::FragmentState {
-            module: &shader,
-            entry_point: "fs_main_bg",
-            targets: &[Some(gpu.config.format.into())],
-        }),
-        primitive: wgpu::PrimitiveState::default(),
-        depth_stencil: None,
-        multisample: wgpu::MultisampleState::default(),
-        multiview: None,
-    });
+    // Score popups each get their own small buffer, reused from frame to frame,
+    // along with the text it was last shaped with
+    let mut popup_buffers: Vec<(String, Buffer)> = vec![];
 
     let mut input = input::Input::default();
-    let mut nut_count = 0;
+    let mut timestep = timestep::FixedTimestep::new();
     let mut color = image::Rgba([255,0,0,255]);
     let mut brush_size = 10_i32;
-    let (img_bg_w, img_bg_h) = img_bg.dimensions();
-
-    #[repr(C)]
-    #[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
-    struct GPUCamera {
-        screen_pos: [f32;2],
-        screen_size: [f32;2]
-    }
-    let camera = GPUCamera {
-        screen_pos: [0.0, 0.0],
-        // Consider using config.width and config.height instead,
-        // it's up to you whether you want the window size to change what's visible in the game
-        // or scale it up and down
-        screen_size: [1024.0, 768.0],
-    };
 
-    // total squirrel is 36x133px with 6 frames
-    // one frame of squirrel is 36x22px
-    let sprite_sheet_dimensions = squirrel_img.dimensions();
-    let squirrel_total_w: f32 = 35.0;
-    let squirrel_total_h: f32 = 174.0;
-    let squirrel_frame_w: f32 = 35.0;
-    let squirrel_frame_h: f32 = 22.5;
-
-    // frames will be a series of frames 
-    let mut squirrel_sheet_positions: Vec<[f32; 4]> = vec![
-
-        // frame 1 sheet position
-        [126.0/162.0, 25.0/174.0, 32.0/162.0, 21.0/174.0],
-
-        // frame 2 sheet position
-        [126.0/162.0, 48.0/174.0, 32.0/162.0, 22.0/174.0],
- 
-        // frame 3 sheet position
-        [126.0/162.0, 72.0/174.0, 28.0/162.0, 23.0/174.0],
-
-        // frame 4 sheet position
-        [126.0/162.0, 97.0/174.0, 35.0/162.0, 23.0/174.0],
-
-        // frame 5 sheet position
-        [126.0/162.0, 122.0/174.0, 33.0/162.0, 22.0/174.0],
-
-    ];
-
-    let mut sprites: Vec<GPUSprite> = vec![
-        // SQUIRREL
-    GPUSprite {
-        screen_region: [32.0, 32.0, 100.0, 100.0],
-        sheet_region: squirrel_sheet_positions[0],   
-    },
-        // NUT
-    GPUSprite {
-        screen_region: [20.0, 200.0, 55.0, 55.0],
-        sheet_region: [0.0, 0.0, 123.0/sprite_sheet_dimensions.0 as f32, 172.0/sprite_sheet_dimensions.1 as f32],   
-    }
-    ];
-
-    let squirrel_animation: Animation = Animation {
-        states: squirrel_sheet_positions,
-        frame_counter: 0,
-        rate: 7,
-        state_number: 0,
-    };
-
-    let acorn_animation: Animation = Animation {
-        states: [sprites[1].sheet_region].to_vec(),
-        frame_counter: 0,
-        rate: 7,
-        state_number: 0,
-    };
-
-    let mut squirrel = char_action::Char_action::new(
-        sprites[0].screen_region,
-        squirrel_animation,
-        2.0,
-        true,
-        0,
-    );
-
-    let mut acorn = char_action::Char_action::new(
-        sprites[1].screen_region,
-        acorn_animation,
-        2.0,
-        true,
-        1,
+    // What part of the world is on screen.  Zoomed all the way out that's the
+    // whole world, however big the window is; it gets scaled to fit.
+    let mut camera = camera::Camera::new([world.size.width, world.size.height]);
+    let gpu_camera = GPUCamera { screen_pos: camera.screen_pos(), screen_size: camera.screen_size() };
+
+    // One background or sprite group per texture, each on a layer: the parallax
+    // layers at the back, then the map and everything that moves, then effects over those
+    let mut sprite_renderer = sprite_game::SpriteRenderer::new(&gpu);
+    // Each background layer fills the screen and scrolls by its UVs, redone
+    // every frame.  Layers that never move go right at the back.
+    let parallax_layers: Vec<(parallax::ParallaxLayer, usize)> = parallax_textures
+        .into_iter()
+        .map(|(layer, tex)| {
+            let on = if layer.is_still() { Layer::Background } else { Layer::Parallax };
+            let background = sprite_renderer.add_background(&gpu, tex, on);
+            (layer, background)
+        })
+        .collect();
+    // The map doesn't move, so its tiles only need writing once
+    let tiles: Vec<GPUSprite> = world
+        .map
+        .sprites()
+        .into_iter()
+        .map(|(screen_region, sheet_region)| GPUSprite::new(screen_region, sheet_region))
+        .collect();
+    let tiles_group = sprite_renderer.add_sprite_group(&gpu, tileset_tex, tiles, gpu_camera, Layer::World);
+    // The squirrel is always first, then however many acorns and pinecones are falling right now
+    let world_group = sprite_renderer.add_sprite_group(
+        &gpu,
+        squirrel_tex,
+        vec![body_sprite(&world.squirrel, 0.0)],
+        gpu_camera,
+        Layer::World,
     );
-
-    let buffer_camera = gpu.device.create_buffer(&wgpu::BufferDescriptor{
-        label: None,
-        size: bytemuck::bytes_of(&camera).len() as u64,
-        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        mapped_at_creation: false
-    });
-    let buffer_sprite = gpu.device.create_buffer(&wgpu::BufferDescriptor{
-        label: None,
-        size: bytemuck::cast_slice::<_,u8>(&sprites).len() as u64,
-        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
-        mapped_at_creation: false
-    });
-
-    gpu.queue.write_buffer(&buffer_camera, 0, bytemuck::bytes_of(&camera));
-    gpu.queue.write_buffer(&buffer_sprite, 0, bytemuck::cast_slice(&sprites));
-
-    let sprite_bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
-        label: None,
-        layout: &sprite_bind_group_layout,
-        entries: &[
-            wgpu::BindGroupEntry {
-                binding: 0,
-                resource: buffer_camera.as_entire_binding()
-            },
-            wgpu::BindGroupEntry {
-                binding: 1,
-                resource: buffer_sprite.as_entire_binding()
-            }
-        ],
-    });
+    // in front of the map on the same layer
+    sprite_renderer.set_depth(world_group, 1);
+    // Whatever was just caught, glowing as it fades out
+    let effects_group = sprite_renderer.add_sprite_group(&gpu, effects_tex, vec![], gpu_camera, Layer::Effects);
+    sprite_renderer.set_blend(effects_group, Blend::Additive);
 
     // Now our setup is all done and we can kick off the windowing event loop.
     // This closure is a "move closure" that claims ownership over variables used within its scope.
@@ -384,18 +174,81 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                 window.request_redraw();
             }
             Event::RedrawRequested(_) => {
-                // TODO: move sprites, maybe scroll camera
-                
+                // The camera moves every step, so tell the sprites where it is now
+                let gpu_camera = GPUCamera { screen_pos: camera.screen_pos(), screen_size: camera.screen_size() };
+                sprite_renderer.set_camera_all(&gpu, gpu_camera);
+
+                // Draw everyone partway between their last two simulated positions
+                let alpha = timestep.alpha();
+                let sprite_count = 1 + world.falling.objects.len();
+                sprite_renderer.resize_sprite_group(&gpu, world_group, sprite_count);
+                let sprites = sprite_renderer.get_sprites_mut(world_group);
+                sprites[world.squirrel.sprites_index] = body_sprite(&world.squirrel, alpha);
+                for (sprite, object) in sprites[1..].iter_mut().zip(&world.falling.objects) {
+                    *sprite = body_sprite(&object.body, alpha);
+                }
+                sprite_renderer.resize_sprite_group(&gpu, effects_group, world.fading.len());
+                let effects = sprite_renderer.get_sprites_mut(effects_group);
+                for (sprite, fade) in effects.iter_mut().zip(&world.fading) {
+                    *sprite = body_sprite(&fade.body, alpha);
+                }
+
+                // drifting goes by simulated time, so it stops while paused and replays match
+                for (layer, background) in parallax_layers.iter() {
+                    let (uv_offset, uv_scale) = layer.uv(gpu_camera.screen_pos, gpu_camera.screen_size, world.clock);
+                    sprite_renderer.set_background_uv(&gpu, *background, GPUBackground { uv_offset, uv_scale });
+                }
+
+                let scene_view = world.gs.scene.render(&world);
+                // The title and end screens are just text over the background
+                sprite_renderer.set_visible(tiles_group, scene_view.show_world);
+                sprite_renderer.set_visible(world_group, scene_view.show_world);
+                sprite_renderer.set_visible(effects_group, scene_view.show_world);
+                if scene_view.text != shown_text {
+                    shown_text = scene_view.text;
+                    buffer.set_text(&mut font_system, &shown_text, Attrs::new().family(Family::SansSerif), Shaping::Advanced);
+                }
+
+                let popups = if scene_view.show_world { &world.popups[..] } else { &[] };
+                popup_buffers.truncate(popups.len());
+                while popup_buffers.len() < popups.len() {
+                    let mut popup_buffer = Buffer::new(&mut font_system, Metrics::new(30.0, 30.0));
+                    popup_buffer.set_size(&mut font_system, 400.0, 40.0);
+                    popup_buffers.push((String::new(), popup_buffer));
+                }
+                for ((text, popup_buffer), popup) in popup_buffers.iter_mut().zip(popups) {
+                    if *text != popup.text {
+                        text.clone_from(&popup.text);
+                        popup_buffer.set_text(&mut font_system, text, Attrs::new().family(Family::SansSerif), Shaping::Advanced);
+                    }
+                }
+                // Popups are placed in world coordinates (y up); text goes by window
+                // pixels from the top left, so the camera converts between them
+                let window_size = [gpu.config.width as f32, gpu.config.height as f32];
+                let text_bounds = TextBounds {
+                    left: 0,
+                    top: 0,
+                    right: gpu.config.width as i32,
+                    bottom: gpu.config.height as i32,
+                };
+                let popup_areas = popup_buffers.iter().zip(popups).map(|((_, popup_buffer), popup)| {
+                    let [left, top] = camera.world_to_screen([popup.x, popup.y], window_size);
+                    TextArea {
+                        buffer: popup_buffer,
+                        left,
+                        top,
+                        scale: 1.0,
+                        bounds: text_bounds,
+                        default_color: Color::rgba(255, 220, 60, (popup.opacity() * 255.0) as u8),
+                    }
+                });
 
                 // Then send the data to the GPU!
-                gpu.queue.write_buffer(&buffer_camera, 0, bytemuck::bytes_of(&camera));
-                gpu.queue.write_buffer(&buffer_sprite, 0, bytemuck::cast_slice(&sprites));
+                sprite_renderer.upload_sprites(&gpu, world_group, 0..sprite_count);
+                sprite_renderer.upload_sprites(&gpu, effects_group, 0..world.fading.len());
                 // ...all the drawing stuff goes here...
                 window.request_redraw();
 
-                // Leave now_keys alone, but copy over all changed keys
-                input.next_frame();
-
                 text_renderer.prepare(
                     &gpu.device,
                     &gpu.queue,
@@ -405,19 +258,15 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                         width: gpu.config.width,
                         height: gpu.config.height,
                     },
-                    [TextArea {
+                    std::iter::once(TextArea {
                         buffer: &buffer,
                         left: 10.0,
                         top: 10.0,
                         scale: 1.0,
-                        bounds: TextBounds {
-                            left: 0,
-                            top: 0,
-                            right: 600,
-                            bottom: 160,
-                        },
+                        bounds: text_bounds,
                         default_color: Color::rgb(255, 255, 255),
-                    }],
+                    })
+                    .chain(popup_areas),
                     &mut cache,
                 ).unwrap();
 
@@ -450,26 +299,10 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                         })],
                         depth_stencil_attachment: None,
                     });
-                    rpass.set_pipeline(&render_pipeline_bg);
-                    // Attach the bind group for group 0
-                    rpass.set_bind_group(0, &tex_bg_bind_group, &[]);
-                    // Now draw two triangles!
-                    rpass.draw(0..6, 0..2);
-
-                    // Now we begin a render pass.  The descriptor tells WGPU that
-                    // we want to draw onto our swapchain texture view (that's where the colors will go)
-                    // and that there's no depth buffer or stencil buffer.
-
+                    sprite_renderer.render(&mut rpass, Layer::Background..=Layer::Effects);
+                    // The HUD goes on top of everything, with its text last of all
+                    sprite_renderer.render(&mut rpass, Layer::Hud..=Layer::Hud);
                     text_renderer.render(&atlas, &mut rpass).unwrap();
-
-                    rpass.set_pipeline(&render_pipeline);
-                    rpass.set_bind_group(0, &sprite_bind_group, &[]);
-                    rpass.set_bind_group(1, &texture_bind_group, &[]);
-                    // // draw two triangles per sprite, and sprites-many sprites.
-                    // // this uses instanced drawing, but it would also be okay
-                    // // to draw 6 * sprites.len() vertices and use modular arithmetic
-                    // // to figure out which sprite we're drawing, instead of the instance index.
-                    rpass.draw(0..6, 0..(sprites.len() as u32));
             }
 
                 // Once the commands have been scheduled, we send them over to the GPU via the queue.
@@ -484,7 +317,18 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
             Event::WindowEvent {
                 event: WindowEvent::CloseRequested,
                 ..
-            } => *control_flow = ControlFlow::Exit,
+            } => {
+                if let (scene::Scene::Playing | scene::Scene::Paused, Some(path)) = (world.gs.scene, save_path.as_ref()) {
+                    save_run(&world, path);
+                }
+                if let (Some(recording), Some(path)) = (recording.as_ref(), opts.record.as_ref()) {
+                    match recording.save(path) {
+                        Ok(()) => log::info!("Saved replay to {}", path.display()),
+                        Err(e) => log::error!("Couldn't save replay to {}: {}", path.display(), e),
+                    }
+                }
+                *control_flow = ControlFlow::Exit
+            }
             // WindowEvent->KeyboardInput: Keyboard input!
             Event::WindowEvent {
                 // Note this deeply nested pattern match
@@ -496,11 +340,25 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
             } => {
             input.handle_key_event(key_ev);
             },
+            // Typed text, for entering a name
+            Event::WindowEvent {
+                event: WindowEvent::ReceivedCharacter(c),
+                ..
+            } => {
+                world.type_char(c);
+            }
             Event::WindowEvent {
                 event: WindowEvent::MouseInput { state, button, .. },
                 ..
             } => {
                 input.handle_mouse_button(state, button);
+                // Nothing in the game is clicked on yet, but it helps to know where things are
+                if state == winit::event::ElementState::Pressed {
+                    let pos = input.mouse_pos();
+                    let window_size = [gpu.config.width as f32, gpu.config.height as f32];
+                    let [x, y] = camera.screen_to_world([pos.x as f32, pos.y as f32], window_size);
+                    log::debug!("{:?} click at ({:.0}, {:.0}) in the world", button, x, y);
+                }
             }
             Event::WindowEvent {
                 event: WindowEvent::CursorMoved { position, .. },
@@ -510,65 +368,60 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
             }
             Event::MainEventsCleared => {
 
-                acorn.move_down();
-
-                if input.is_key_down(winit::event::VirtualKeyCode::Left) {
-
-                    squirrel.face_left();
-                    squirrel.walk();
-                    squirrel.animation.tick();
-                    
+                // Run however many fixed steps fit into the time since the last frame
+                let steps = timestep.advance();
+                let mut live_frame = input.frame();
+                for _ in 0..steps {
+                    // Replays feed their own inputs until they run out, then the keyboard takes over
+                    let frame = playback.as_mut().and_then(|p| p.next_frame()).unwrap_or(live_frame);
+                    if let Some(recording) = recording.as_mut() {
+                        recording.record(frame);
+                    }
+                    world.step(&frame);
+                    let squirrel = collision::Rect::from_region(world.squirrel.screen_region);
+                    camera.update(squirrel.center(), world.size, timestep::DT);
+                    // A key press only counts for the first step it shows up in
+                    live_frame = live_frame.held();
                 }
-                else if input.is_key_down(winit::event::VirtualKeyCode::Right) {
-
-                    squirrel.face_right();
-                    squirrel.walk();
-                    squirrel.animation.tick();
-
+                if steps > 0 {
+                    // Zooming is only for looking, so it's not part of what the World sees
+                    if input.is_key_pressed(input::Key::Equals) || input.is_key_pressed(input::Key::NumpadAdd) {
+                        camera.set_zoom(camera.zoom * camera::ZOOM_STEP);
+                    }
+                    if input.is_key_pressed(input::Key::Minus) || input.is_key_pressed(input::Key::NumpadSubtract) {
+                        camera.set_zoom(camera.zoom / camera::ZOOM_STEP);
+                    }
+                    // Leave now_keys alone, but copy over all changed keys
+                    input.next_frame();
                 }
-                else if input.is_key_up(winit::event::VirtualKeyCode::Left)  || input.is_key_up(winit::event::VirtualKeyCode::Right){
-                    squirrel.animation.stop();
+                let mut save_high_scores = false;
+                let mut paused = false;
+                let mut run_over = false;
+                for event in world.drain_events() {
+                    log::debug!("{:?}", event);
+                    save_high_scores |= event == world::GameEvent::HighScore;
+                    paused |= event == world::GameEvent::Paused;
+                    run_over |= event == world::GameEvent::RunOver;
+                    if event == world::GameEvent::Hit {
+                        camera.add_trauma(camera::HIT_TRAUMA);
+                    }
                 }
-
-                sprites[squirrel.sprites_index].sheet_region = squirrel.animation.get_current_state();
-                sprites[squirrel.sprites_index].screen_region = squirrel.screen_region;
-
-                sprites[acorn.sprites_index].screen_region = acorn.screen_region;
-
-                let acorn_x: f32 = sprites[acorn.sprites_index].screen_region[0];
-                let acorn_y: f32 = sprites[acorn.sprites_index].screen_region[1];
-                let acorn_width: f32 = sprites[acorn.sprites_index].screen_region[2];
-                let acorn_height: f32 = sprites[acorn.sprites_index].screen_region[3];
-
-                let mut squirrel_x: f32 = sprites[squirrel.sprites_index].screen_region[0];
-                let squirrel_y: f32 = sprites[squirrel.sprites_index].screen_region[1];
-                let mut squirrel_width: f32 = sprites[squirrel.sprites_index].screen_region[2];
-                let squirrel_height: f32 = sprites[squirrel.sprites_index].screen_region[3];
-
-                // adjusting for right facing squirrel
-                if squirrel.facing_right {
-                    squirrel_x += squirrel_width;
-                    squirrel_width *= -1.0;
+                if let Some(path) = save_path.as_ref() {
+                    if run_over {
+                        if let Err(e) = std::fs::remove_file(path) {
+                            if e.kind() != std::io::ErrorKind::NotFound {
+                                log::warn!("Couldn't remove saved run {}: {}", path.display(), e);
+                            }
+                        }
+                    } else if paused {
+                        save_run(&world, path);
+                    }
                 }
-
-                // Check for collisions
-                if (acorn_x + acorn_width > squirrel_x) && (acorn_x < squirrel_x + squirrel_width)
-                    && (acorn_y - acorn_height < squirrel_y) && (acorn_y > squirrel_y - squirrel_height) {
-                    // Collision detected, handle it here
-                    nut_count += 1;
-                    acorn.speed += 0.1;
-                    acorn.reset_y();
-
-                    if !gs.score_changing{
-                        gs.score += 1;
-                        let score_text = format!("Score: {}", gs.score);
-                        // buffer.set_text(&mut font_system, &gs.score.to_string(), Attrs::new().family(Family::SansSerif), Shaping::Advanced);    
-                        buffer.set_text(&mut font_system, &score_text, Attrs::new().family(Family::SansSerif), Shaping::Advanced);
-                        gs.score_changing = true;
+                if let (true, Some(path)) = (save_high_scores, high_score_path.as_ref()) {
+                    if let Err(e) = world.high_scores.save(path) {
+                        log::error!("Couldn't save high scores to {}: {}", path.display(), e);
                     }
-
                 }
-                else{gs.score_changing = false;}
 
                 window.request_redraw();
             }
@@ -577,17 +430,112 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
     });
 }
 
+// How a character gets drawn, partway (alpha) between its last two positions
+fn body_sprite(body: &char_action::Char_action, alpha: f32) -> GPUSprite {
+    let flags = if body.flip_x { FLIP_X } else { 0 };
+    GPUSprite::new(body.interpolated_region(alpha), body.animation.get_current_state())
+        .with_flags(flags)
+        .with_tint(body.tint)
+        .with_rotation(body.rotation)
+        .with_scale(body.scale)
+}
+
+// The world to start with, the replay to play back if there is one, and the
+// level file it's on
+type Loaded = (world::World, Option<replay::Playback>, Option<PathBuf>);
+
+// Load everything the command line points at before opening a window, so a bad
+// file is reported like a bad argument rather than after the game has started
+fn load(opts: &options::Options) -> Result<Loaded, String> {
+    // A replay brings its own seed and level along
+    let playback = match opts.replay.as_ref() {
+        Some(path) => Some(
+            replay::Replay::load(path)
+                .map_err(|e| format!("Couldn't load replay {}: {}", path.display(), e))?
+                .play(),
+        ),
+        None => None,
+    };
+    let seed = playback.as_ref().map_or(opts.seed, |p| p.seed());
+    log::info!("Starting with seed {}", seed);
+    let mut world = world::World::new(seed);
+    // Read the tiers again from disk so they can be tuned without a rebuild
+    match difficulty::Difficulty::load("content/difficulty.ron") {
+        Ok(difficulty) => world.difficulty = difficulty,
+        Err(e) => log::warn!("{}; using the built-in difficulty", e),
+    }
+    // A level picked on the command line (or by the replay) has to load, since
+    // that's what was asked for; otherwise it's the endless game on the forest map
+    let level_path = match playback.as_ref() {
+        Some(playback) => {
+            if opts.level.is_some() {
+                log::warn!("Ignoring --level; the replay says which level it was recorded on");
+            }
+            playback.level().map(PathBuf::from)
+        }
+        None => opts.level.clone(),
+    };
+    match level_path.as_ref() {
+        Some(path) => {
+            let level = level::Level::load(path)
+                .map_err(|e| format!("Couldn't load level {}: {}", path.display(), e))?;
+            log::info!("Playing {} ({} waves)", level.name, level.waves.len());
+            if let Some(map) = level.map.as_ref() {
+                let map_path = path.parent().unwrap_or(Path::new("")).join(map);
+                world.map = tilemap::Tilemap::load(&map_path)
+                    .map_err(|e| format!("Couldn't load map {}: {}", map_path.display(), e))?;
+            }
+            world.level = Some(level);
+        }
+        None => match tilemap::Tilemap::load("content/levels/forest.csv") {
+            Ok(map) => world.map = map,
+            Err(e) => log::warn!("{}; using the built-in map", e),
+        },
+    }
+    if let Some(playback) = playback.as_ref() {
+        if let Err(e) = playback.check_content(world.content_hash()) {
+            return Err(format!("Can't play back {}: {}", opts.replay.as_ref().unwrap().display(), e));
+        }
+    }
+    Ok((world, playback, level_path))
+}
+
+// Write out the run in progress so the next launch can pick it back up
+fn save_run(world: &world::World, path: &Path) {
+    match savegame::SaveGame::of(world).save(path) {
+        Ok(()) => log::info!("Saved the run to {}", path.display()),
+        Err(e) => log::error!("Couldn't save the run to {}: {}", path.display(), e),
+    }
+}
+
 // Main is just going to configure an event loop, open a window, set up logging,
 // and kick off our `run` function.
 fn main() {
+    let opts = match options::parse_args(std::env::args()) {
+        Ok(opts) => opts,
+        Err(msg) => {
+            eprintln!("{}", msg);
+            eprintln!("usage: interactive-drawing [--seed N] [--record FILE] [--replay FILE] [--level FILE]");
+            std::process::exit(2);
+        }
+    };
+    // Logging first, so loading can warn about what it falls back on
+    #[cfg(not(target_arch = "wasm32"))]
+    env_logger::init();
+    let loaded = match load(&opts) {
+        Ok(loaded) => loaded,
+        Err(msg) => {
+            eprintln!("{}", msg);
+            std::process::exit(2);
+        }
+    };
     let event_loop = EventLoop::new();
     let window = winit::window::Window::new(&event_loop).unwrap();
 
     #[cfg(not(target_arch = "wasm32"))]
     {
-        env_logger::init();
         // On native, we just want to wait for `run` to finish.
-        pollster::block_on(run(event_loop, window));
+        pollster::block_on(run(event_loop, window, opts, loaded));
     }
     #[cfg(target_arch = "wasm32")]
     {
@@ -605,6 +553,6 @@ fn main() {
             })
             .expect("couldn't append canvas to document body");
         // Now we use the browser's runtime to spawn our async run function.
-        wasm_bindgen_futures::spawn_local(run(event_loop, window));
+        wasm_bindgen_futures::spawn_local(run(event_loop, window, opts, loaded));
     }
 }
\ No newline at end of file
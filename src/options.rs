@@ -0,0 +1,48 @@
+use std::path::PathBuf;
+
+// Command-line options.  There are few enough of them that we parse by hand.
+pub struct Options {
+    pub seed: u64,
+    // write every step's input here when the game closes
+    pub record: Option<PathBuf>,
+    // play these inputs back instead of reading the keyboard
+    pub replay: Option<PathBuf>,
+    // play through this level's waves instead of the endless game
+    pub level: Option<PathBuf>,
+}
+
+pub fn parse_args(args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut seed = None;
+    let mut record = None;
+    let mut replay = None;
+    let mut level = None;
+    let mut args = args.skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => {
+                let value = args.next().ok_or("--seed needs a number after it")?;
+                let parsed = value
+                    .parse::<u64>()
+                    .map_err(|e| format!("bad --seed {:?}: {}", value, e))?;
+                seed = Some(parsed);
+            }
+            "--record" => {
+                record = Some(PathBuf::from(args.next().ok_or("--record needs a file path")?));
+            }
+            "--replay" => {
+                replay = Some(PathBuf::from(args.next().ok_or("--replay needs a file path")?));
+            }
+            "--level" => {
+                level = Some(PathBuf::from(args.next().ok_or("--level needs a file path")?));
+            }
+            other => return Err(format!("unknown argument {:?}", other)),
+        }
+    }
+    Ok(Options {
+        // No seed given?  Pick one, but it still gets logged so the run can be repeated.
+        seed: seed.unwrap_or_else(rand::random),
+        record,
+        replay,
+        level,
+    })
+}
@@ -0,0 +1,125 @@
+use std::fmt;
+use std::path::Path;
+
+use serde::Deserialize;
+
+// The layers that ship with the game; content/parallax.ron is read again at
+// startup so they can be changed without recompiling.
+const BUILT_IN: &str = include_str!("../content/parallax.ron");
+
+// Scrolling background layers, drawn back to front behind everything else
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Parallax {
+    pub layers: Vec<ParallaxLayer>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ParallaxLayer {
+    // relative to content/
+    pub texture: String,
+    // how far it moves per pixel the camera moves: 0 is stuck to the screen,
+    // 1 moves with the world
+    #[serde(default)]
+    pub scroll: f32,
+    // pixels per second it moves on its own
+    #[serde(default)]
+    pub drift: f32,
+    // how wide one copy of the texture is on screen; the screen's width if left out
+    #[serde(default)]
+    pub width: Option<f32>,
+}
+
+#[derive(Debug)]
+pub enum ParallaxError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    Invalid(String),
+}
+
+impl fmt::Display for ParallaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParallaxError::Io(e) => write!(f, "couldn't read parallax file: {}", e),
+            ParallaxError::Parse(e) => write!(f, "couldn't parse parallax file: {}", e),
+            ParallaxError::Invalid(msg) => write!(f, "bad parallax file: {}", msg),
+        }
+    }
+}
+
+impl From<std::io::Error> for ParallaxError {
+    fn from(e: std::io::Error) -> Self {
+        ParallaxError::Io(e)
+    }
+}
+
+impl Default for Parallax {
+    fn default() -> Self {
+        Parallax::from_ron(BUILT_IN).expect("built-in parallax.ron is broken")
+    }
+}
+
+impl Parallax {
+    pub fn load(path: impl AsRef<Path>) -> Result<Parallax, ParallaxError> {
+        let text = std::fs::read_to_string(path)?;
+        Parallax::from_ron(&text)
+    }
+
+    pub fn from_ron(text: &str) -> Result<Parallax, ParallaxError> {
+        let parallax: Parallax = ron::from_str(text).map_err(ParallaxError::Parse)?;
+        for (i, layer) in parallax.layers.iter().enumerate() {
+            if layer.width.is_some_and(|w| w <= 0.0) {
+                return Err(ParallaxError::Invalid(format!("layers[{}].width has to be positive", i)));
+            }
+        }
+        Ok(parallax)
+    }
+}
+
+impl ParallaxLayer {
+    // (uv_offset, uv_scale) to draw this layer with, for a camera at screen_pos
+    // showing screen_size, seconds into the simulation.  The layer always fills
+    // the screen; the scrolling is all in the UVs, which go past 0..1 and rely
+    // on the sampler repeating the texture.
+    pub fn uv(&self, screen_pos: [f32; 2], screen_size: [f32; 2], seconds: f32) -> ([f32; 2], [f32; 2]) {
+        let width = self.width.unwrap_or(screen_size[0]);
+        let offset = (screen_pos[0] * self.scroll - self.drift * seconds) / width;
+        // only the fraction matters, and keeping it small keeps the float math exact
+        let offset = offset.rem_euclid(1.0);
+        ([offset, 0.0], [screen_size[0] / width, 1.0])
+    }
+
+    // Doesn't scroll or drift, so it can go right at the back
+    pub fn is_still(&self) -> bool {
+        self.scroll == 0.0 && self.drift == 0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layers_scroll_and_drift() {
+        let layer = ParallaxLayer { texture: "t.png".to_string(), scroll: 0.5, drift: 0.0, width: Some(400.0) };
+        // half as far as the camera, in fractions of the texture
+        assert_eq!(layer.uv([0.0, 0.0], [800.0, 600.0], 0.0), ([0.0, 0.0], [2.0, 1.0]));
+        assert_eq!(layer.uv([100.0, 0.0], [800.0, 600.0], 0.0).0[0], 0.125);
+        assert!(!layer.is_still());
+        // drifting left shows more of what's to the right, and wraps around
+        let clouds = ParallaxLayer { scroll: 0.0, drift: -100.0, ..layer };
+        assert_eq!(clouds.uv([0.0, 0.0], [800.0, 600.0], 1.0).0[0], 0.25);
+        assert_eq!(clouds.uv([0.0, 0.0], [800.0, 600.0], 5.0).0[0], 0.25);
+    }
+
+    #[test]
+    fn built_in_layers_load() {
+        let parallax = Parallax::default();
+        assert_eq!(parallax.layers[0].width, None);
+        assert!(parallax.layers[0].is_still());
+        assert!(parallax.layers.iter().all(|layer| Path::new("content").join(&layer.texture).exists()));
+        let err = Parallax::from_ron("(layers: [(texture: \"a.png\", width: Some(0.0))])").unwrap_err();
+        assert_eq!(err.to_string(), "bad parallax file: layers[0].width has to be positive");
+    }
+}
@@ -0,0 +1,178 @@
+use serde::{Deserialize, Serialize};
+
+// pixels per second per second, pulling down
+pub const GRAVITY: f32 = 1800.0;
+// how fast a jump leaves the ground; with GRAVITY that's about 170px up at most
+pub const JUMP_SPEED: f32 = 780.0;
+// letting go of jump early drops the upward speed to this, for a short hop
+pub const JUMP_CUT_SPEED: f32 = 250.0;
+// after running off an edge there's still this long to jump, in seconds
+pub const COYOTE_SECONDS: f32 = 0.1;
+// nothing falls faster than this, however long it's been falling
+pub const MAX_FALL_SPEED: f32 = 1200.0;
+
+// Velocity, acceleration and ground contact for something that can jump around.
+// It doesn't own a position: step() moves whatever [x, y] it's handed, in the
+// same screen coordinates as the sprites (y goes up), so it can be tested
+// without any sprites at all.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Physics {
+    pub velocity: [f32; 2],
+    // on top of gravity
+    pub acceleration: [f32; 2],
+    pub gravity: f32,
+    pub on_ground: bool,
+    // seconds left to jump after leaving the ground without jumping
+    pub coyote: f32,
+    // whether jump was held last step, so holding it down doesn't keep jumping
+    pub jump_held: bool,
+}
+
+impl Default for Physics {
+    fn default() -> Self {
+        Physics {
+            velocity: [0.0, 0.0],
+            acceleration: [0.0, 0.0],
+            gravity: GRAVITY,
+            on_ground: true,
+            coyote: COYOTE_SECONDS,
+            jump_held: false,
+        }
+    }
+}
+
+impl Physics {
+    // Call once a step with whether jump is held right now.  Pressing it while
+    // on the ground (or just off it) jumps; letting go on the way up cuts the
+    // jump short, so a tap is a hop and holding it goes all the way.
+    pub fn jump_input(&mut self, held: bool) {
+        if held && !self.jump_held && (self.on_ground || self.coyote > 0.0) {
+            self.velocity[1] = JUMP_SPEED;
+            self.on_ground = false;
+            self.coyote = 0.0;
+        } else if !held && self.jump_held && self.velocity[1] > JUMP_CUT_SPEED {
+            self.velocity[1] = JUMP_CUT_SPEED;
+        }
+        self.jump_held = held;
+    }
+
+    // Move pos along by dt seconds.  ground is the height of whatever's under
+    // pos right now (f32::NEG_INFINITY if there's nothing); coming down onto
+    // it stops the fall there.
+    pub fn step(&mut self, pos: &mut [f32; 2], ground: f32, dt: f32) {
+        // semi-implicit Euler: speed first, then move by the new speed
+        self.velocity[0] += self.acceleration[0] * dt;
+        self.velocity[1] += (self.acceleration[1] - self.gravity) * dt;
+        self.velocity[1] = self.velocity[1].max(-MAX_FALL_SPEED);
+        pos[0] += self.velocity[0] * dt;
+        pos[1] += self.velocity[1] * dt;
+
+        if self.velocity[1] <= 0.0 && pos[1] <= ground {
+            pos[1] = ground;
+            self.velocity[1] = 0.0;
+            self.on_ground = true;
+            self.coyote = COYOTE_SECONDS;
+        } else {
+            self.on_ground = false;
+            self.coyote = (self.coyote - dt).max(0.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: f32 = 1.0 / 60.0;
+
+    // Jump off flat ground at y = 0, holding jump for this many steps, and see how high it gets
+    fn peak(hold_steps: usize) -> f32 {
+        let mut physics = Physics::default();
+        let mut pos = [0.0, 0.0];
+        let mut peak = 0.0_f32;
+        for i in 0..240 {
+            physics.jump_input(i < hold_steps);
+            physics.step(&mut pos, 0.0, DT);
+            peak = peak.max(pos[1]);
+        }
+        assert!(physics.on_ground);
+        assert_eq!(pos[1], 0.0);
+        peak
+    }
+
+    #[test]
+    fn standing_still_stays_put() {
+        let mut physics = Physics::default();
+        let mut pos = [10.0, 32.0];
+        for _ in 0..60 {
+            physics.step(&mut pos, 32.0, DT);
+        }
+        assert_eq!(pos, [10.0, 32.0]);
+        assert!(physics.on_ground);
+    }
+
+    #[test]
+    fn jumps_go_up_and_come_back_down() {
+        let full = peak(240);
+        // v^2 / 2g, give or take the step size
+        let expected = JUMP_SPEED * JUMP_SPEED / (2.0 * GRAVITY);
+        assert!((full - expected).abs() < 10.0, "{} vs {}", full, expected);
+    }
+
+    #[test]
+    fn letting_go_early_jumps_lower() {
+        let (tap, half, full) = (peak(1), peak(10), peak(240));
+        assert!(tap < half && half < full, "{} {} {}", tap, half, full);
+        assert!(tap < full / 3.0);
+    }
+
+    #[test]
+    fn holding_jump_only_jumps_once() {
+        let mut physics = Physics::default();
+        let mut pos = [0.0, 0.0];
+        let mut landings = 0;
+        for _ in 0..600 {
+            let was_on_ground = physics.on_ground;
+            physics.jump_input(true);
+            physics.step(&mut pos, 0.0, DT);
+            if physics.on_ground && !was_on_ground {
+                landings += 1;
+            }
+        }
+        assert_eq!(landings, 1);
+    }
+
+    #[test]
+    fn coyote_time_allows_a_late_jump() {
+        // standing on a ledge at y = 100, then walking off it
+        let mut physics = Physics::default();
+        let mut pos = [0.0, 100.0];
+        physics.step(&mut pos, 100.0, DT);
+        let steps = (COYOTE_SECONDS / DT) as usize - 1;
+        for _ in 0..steps {
+            physics.step(&mut pos, f32::NEG_INFINITY, DT);
+        }
+        assert!(!physics.on_ground && pos[1] < 100.0);
+        physics.jump_input(true);
+        assert!(physics.velocity[1] > 0.0);
+
+        // but not once it's run out
+        let mut physics = Physics::default();
+        let mut pos = [0.0, 100.0];
+        for _ in 0..steps + 3 {
+            physics.step(&mut pos, f32::NEG_INFINITY, DT);
+        }
+        physics.jump_input(true);
+        assert!(physics.velocity[1] < 0.0);
+    }
+
+    #[test]
+    fn falls_no_faster_than_the_limit() {
+        let mut physics = Physics::default();
+        let mut pos = [0.0, 0.0];
+        for _ in 0..600 {
+            physics.step(&mut pos, f32::NEG_INFINITY, DT);
+        }
+        assert_eq!(physics.velocity[1], -MAX_FALL_SPEED);
+    }
+}
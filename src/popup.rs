@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+
+// how long a popup stays on screen, in seconds
+pub const POPUP_SECONDS: f32 = 0.8;
+// how fast it drifts up while it fades, in pixels per second
+const POPUP_RISE: f32 = 60.0;
+
+// A little bit of floating text, like "+3", left where something was caught.
+// x and y are in the same screen coordinates as the sprites (y goes up).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Popup {
+    pub text: String,
+    pub x: f32,
+    pub y: f32,
+    // seconds since it appeared
+    pub age: f32,
+}
+
+impl Popup {
+    pub fn new(text: String, x: f32, y: f32) -> Popup {
+        Popup { text, x, y, age: 0.0 }
+    }
+
+    // 1.0 when it first appears, fading to 0.0 as it goes away
+    pub fn opacity(&self) -> f32 {
+        (1.0 - self.age / POPUP_SECONDS).clamp(0.0, 1.0)
+    }
+}
+
+// Float every popup up a bit and drop the ones that have faded out
+pub fn update(popups: &mut Vec<Popup>, dt: f32) {
+    popups.retain_mut(|popup| {
+        popup.age += dt;
+        popup.y += POPUP_RISE * dt;
+        popup.age < POPUP_SECONDS
+    });
+}
@@ -0,0 +1,123 @@
+use serde::{Deserialize, Serialize};
+
+// Power-ups drop now and then along with the acorns.  Catching one turns it on
+// for a while; catching the same kind again while it's still going adds its
+// time on top, up to MAX_STACKS worth.  Different kinds all run at once.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PowerUp {
+    // walk faster
+    Speed,
+    // pull nearby acorns in
+    Magnet,
+    // acorns are worth twice as much
+    DoublePoints,
+    // the next hazard bounces off
+    Shield,
+}
+
+// how many durations' worth of one power-up can be banked at once
+pub const MAX_STACKS: f32 = 2.0;
+
+impl PowerUp {
+    pub const ALL: [PowerUp; 4] = [PowerUp::Speed, PowerUp::Magnet, PowerUp::DoublePoints, PowerUp::Shield];
+
+    // seconds one pickup lasts
+    pub fn duration(self) -> f32 {
+        match self {
+            PowerUp::Speed => 6.0,
+            PowerUp::Magnet => 8.0,
+            PowerUp::DoublePoints => 10.0,
+            PowerUp::Shield => 15.0,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PowerUp::Speed => "Speed",
+            PowerUp::Magnet => "Magnet",
+            PowerUp::DoublePoints => "x2",
+            PowerUp::Shield => "Shield",
+        }
+    }
+}
+
+// Seconds left on each power-up, 0.0 when it's off
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PowerUps {
+    timers: [f32; 4],
+}
+
+impl PowerUps {
+    pub fn collect(&mut self, power_up: PowerUp) {
+        let timer = &mut self.timers[power_up as usize];
+        *timer = (*timer + power_up.duration()).min(power_up.duration() * MAX_STACKS);
+    }
+
+    pub fn tick(&mut self, dt: f32) {
+        for timer in self.timers.iter_mut() {
+            *timer = (*timer - dt).max(0.0);
+        }
+    }
+
+    pub fn active(&self, power_up: PowerUp) -> bool {
+        self.remaining(power_up) > 0.0
+    }
+
+    pub fn remaining(&self, power_up: PowerUp) -> f32 {
+        self.timers[power_up as usize]
+    }
+
+    // A shield only takes one hit.  Returns whether there was one to use up.
+    pub fn use_shield(&mut self) -> bool {
+        let had_one = self.active(PowerUp::Shield);
+        self.timers[PowerUp::Shield as usize] = 0.0;
+        had_one
+    }
+
+    // What each acorn is worth right now
+    pub fn multiplier(&self) -> usize {
+        if self.active(PowerUp::DoublePoints) {
+            2
+        } else {
+            1
+        }
+    }
+
+    // Everything that's on, with how long it has left, in a fixed order
+    pub fn running(&self) -> impl Iterator<Item = (PowerUp, f32)> + '_ {
+        PowerUp::ALL
+            .into_iter()
+            .filter(|p| self.active(*p))
+            .map(|p| (p, self.remaining(p)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_kind_stacks_up_to_the_cap() {
+        let mut power_ups = PowerUps::default();
+        power_ups.collect(PowerUp::Speed);
+        power_ups.tick(1.0);
+        power_ups.collect(PowerUp::Speed);
+        assert_eq!(power_ups.remaining(PowerUp::Speed), 11.0);
+        power_ups.collect(PowerUp::Speed);
+        assert_eq!(power_ups.remaining(PowerUp::Speed), 12.0);
+        assert!(!power_ups.active(PowerUp::Magnet));
+    }
+
+    #[test]
+    fn timers_run_out() {
+        let mut power_ups = PowerUps::default();
+        power_ups.collect(PowerUp::DoublePoints);
+        power_ups.collect(PowerUp::Magnet);
+        assert_eq!(power_ups.multiplier(), 2);
+        power_ups.tick(9.0);
+        assert_eq!(power_ups.running().collect::<Vec<_>>(), vec![(PowerUp::DoublePoints, 1.0)]);
+        power_ups.tick(1.0);
+        assert_eq!(power_ups.multiplier(), 1);
+        assert_eq!(power_ups.running().count(), 0);
+    }
+}
@@ -0,0 +1,296 @@
+use std::fmt;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use crate::input::InputFrame;
+
+// Replay files are tiny and binary, all little-endian:
+//   b"NUTR", version: u16, seed: u64,
+//   level path length: u16 (0 for the endless game), level path in UTF-8,
+//   content hash: u64, frame count: u32,
+//   then one byte of InputFrame bits per simulation step.
+const MAGIC: &[u8; 4] = b"NUTR";
+const VERSION: u16 = 3;
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(std::io::Error),
+    NotAReplay,
+    UnsupportedVersion(u16),
+    // the level, difficulty or map files differ from the ones it was recorded with
+    ContentChanged,
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(e) => write!(f, "couldn't read or write replay: {}", e),
+            ReplayError::NotAReplay => write!(f, "not a replay file"),
+            ReplayError::UnsupportedVersion(v) => {
+                write!(f, "replay version {} is not supported (expected {})", v, VERSION)
+            }
+            ReplayError::ContentChanged => write!(
+                f,
+                "the level, difficulty or map files have changed since this replay was recorded"
+            ),
+        }
+    }
+}
+
+impl From<std::io::Error> for ReplayError {
+    fn from(e: std::io::Error) -> Self {
+        ReplayError::Io(e)
+    }
+}
+
+// Everything needed to re-run a game exactly: the seed, what it was played on
+// and what was held each step.
+#[derive(Debug, PartialEq)]
+pub struct Replay {
+    pub seed: u64,
+    // the --level it was recorded with, as given
+    pub level: Option<String>,
+    // World::content_hash of the level, difficulty and map it was recorded with
+    pub content_hash: u64,
+    pub frames: Vec<InputFrame>,
+}
+
+impl Replay {
+    pub fn new(seed: u64, level: Option<String>, content_hash: u64) -> Replay {
+        Replay {
+            seed,
+            level,
+            content_hash,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, frame: InputFrame) {
+        self.frames.push(frame);
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ReplayError> {
+        let file = std::fs::File::create(path)?;
+        self.write_to(std::io::BufWriter::new(file))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Replay, ReplayError> {
+        let file = std::fs::File::open(path)?;
+        Replay::read_from(std::io::BufReader::new(file))
+    }
+
+    pub fn write_to(&self, mut w: impl Write) -> Result<(), ReplayError> {
+        w.write_all(MAGIC)?;
+        w.write_all(&VERSION.to_le_bytes())?;
+        w.write_all(&self.seed.to_le_bytes())?;
+        let level = self.level.as_deref().unwrap_or("").as_bytes();
+        w.write_all(&(level.len() as u16).to_le_bytes())?;
+        w.write_all(level)?;
+        w.write_all(&self.content_hash.to_le_bytes())?;
+        w.write_all(&(self.frames.len() as u32).to_le_bytes())?;
+        let bits: Vec<u8> = self.frames.iter().map(|f| f.to_bits()).collect();
+        w.write_all(&bits)?;
+        w.flush()?;
+        Ok(())
+    }
+
+    pub fn read_from(mut r: impl Read) -> Result<Replay, ReplayError> {
+        let mut magic = [0_u8; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(ReplayError::NotAReplay);
+        }
+        let mut version = [0_u8; 2];
+        r.read_exact(&mut version)?;
+        let version = u16::from_le_bytes(version);
+        if version != VERSION {
+            return Err(ReplayError::UnsupportedVersion(version));
+        }
+        let mut seed = [0_u8; 8];
+        r.read_exact(&mut seed)?;
+        let mut level_len = [0_u8; 2];
+        r.read_exact(&mut level_len)?;
+        let mut level = vec![0_u8; u16::from_le_bytes(level_len) as usize];
+        r.read_exact(&mut level)?;
+        let level = String::from_utf8(level).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        let mut content_hash = [0_u8; 8];
+        r.read_exact(&mut content_hash)?;
+        let mut count = [0_u8; 4];
+        r.read_exact(&mut count)?;
+        let mut bits = vec![0_u8; u32::from_le_bytes(count) as usize];
+        r.read_exact(&mut bits)?;
+        Ok(Replay {
+            seed: u64::from_le_bytes(seed),
+            level: Some(level).filter(|level| !level.is_empty()),
+            content_hash: u64::from_le_bytes(content_hash),
+            frames: bits.into_iter().map(InputFrame::from_bits).collect(),
+        })
+    }
+
+    pub fn play(self) -> Playback {
+        Playback {
+            replay: self,
+            next: 0,
+        }
+    }
+}
+
+// Hands out a replay's frames one simulation step at a time.
+pub struct Playback {
+    replay: Replay,
+    next: usize,
+}
+
+impl Playback {
+    pub fn seed(&self) -> u64 {
+        self.replay.seed
+    }
+
+    pub fn level(&self) -> Option<&str> {
+        self.replay.level.as_deref()
+    }
+
+    // Playing the inputs back on anything but what they were recorded on
+    // would wander off into a different game
+    pub fn check_content(&self, content_hash: u64) -> Result<(), ReplayError> {
+        if content_hash == self.replay.content_hash {
+            Ok(())
+        } else {
+            Err(ReplayError::ContentChanged)
+        }
+    }
+
+    // None once the recording has run out
+    pub fn next_frame(&mut self) -> Option<InputFrame> {
+        let frame = self.replay.frames.get(self.next).copied();
+        self.next += 1;
+        frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::Scene;
+    use crate::world::{GameEvent, World};
+
+    fn run(replay: &Replay) -> World {
+        let mut world = World::new(replay.seed);
+        for frame in replay.frames.iter() {
+            world.step(frame);
+        }
+        world
+    }
+
+    #[test]
+    fn replay_round_trips_through_bytes() {
+        let mut replay = Replay::new(7, Some("content/levels/forest.ron".to_string()), 42);
+        for i in 0..500 {
+            replay.record(InputFrame {
+                left: i % 3 == 0,
+                right: i % 5 == 0,
+                jump: i % 7 < 3,
+                pause: i == 100,
+                start: i == 0,
+            });
+        }
+        let mut bytes = vec![];
+        replay.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), 4 + 2 + 8 + 2 + 25 + 8 + 4 + 500);
+        assert_eq!(Replay::read_from(bytes.as_slice()).unwrap(), replay);
+
+        // and without a level
+        let replay = Replay::new(7, None, 42);
+        let mut bytes = vec![];
+        replay.write_to(&mut bytes).unwrap();
+        assert_eq!(Replay::read_from(bytes.as_slice()).unwrap().level, None);
+    }
+
+    #[test]
+    fn replayed_run_reaches_the_same_score() {
+        // Chase the acorn like a player would, recording what we pressed
+        let mut live = World::new(1234);
+        let mut replay = Replay::new(1234, None, live.content_hash());
+        let start = InputFrame { start: true, ..Default::default() };
+        replay.record(start);
+        live.step(&start);
+        for _ in 0..60 * 60 {
+            // head for the middle of whichever acorn is closest to the ground
+            let [x, _, w, _] = live.squirrel.screen_region;
+            let squirrel_mid = x + w / 2.0;
+            let acorn_mid = live
+                .falling
+                .objects
+                .iter()
+                .map(|f| f.body.screen_region)
+                .min_by(|a, b| a[1].total_cmp(&b[1]))
+                .map_or(squirrel_mid, |a| a[0] + a[2] / 2.0);
+            let frame = InputFrame {
+                left: acorn_mid < squirrel_mid - 30.0,
+                right: acorn_mid > squirrel_mid + 30.0,
+                ..Default::default()
+            };
+            replay.record(frame);
+            live.step(&frame);
+        }
+        assert!(live.gs.score > 0);
+
+        let mut bytes = vec![];
+        replay.write_to(&mut bytes).unwrap();
+        let replayed = run(&Replay::read_from(bytes.as_slice()).unwrap());
+        assert_eq!(replayed.gs.score, live.gs.score);
+        assert_eq!(replayed.squirrel.screen_region, live.squirrel.screen_region);
+    }
+
+    #[test]
+    fn replay_carries_on_past_game_over() {
+        // Catch what's easy for a while, then stop trying.  Once the run is
+        // over keep pressing Enter: through name entry, the table, the title
+        // screen and into another run.
+        let mut live = World::new(99);
+        let mut replay = Replay::new(99, None, live.content_hash());
+        let mut runs_over = 0;
+        for step in 0..60 * 120 {
+            let [x, _, w, _] = live.squirrel.screen_region;
+            let acorn_mid = live.falling.objects.first().map_or(x, |f| f.body.screen_region[0] + f.body.screen_region[2] / 2.0);
+            let chasing = runs_over == 0 && live.gs.elapsed < 10.0;
+            let frame = InputFrame {
+                left: chasing && acorn_mid < x + w / 2.0 - 30.0,
+                right: chasing && acorn_mid > x + w / 2.0 + 30.0,
+                start: live.gs.scene != Scene::Playing && step % 20 == 0,
+                ..Default::default()
+            };
+            replay.record(frame);
+            live.step(&frame);
+            if live.drain_events().any(|e| e == GameEvent::RunOver) {
+                runs_over += 1;
+            }
+        }
+        // it got all the way around at least once, onto the table on the way
+        assert!(runs_over >= 2, "only {} runs over", runs_over);
+        assert!(!live.high_scores.entries.is_empty());
+
+        let replayed = run(&replay);
+        assert_eq!(replayed.gs.scene, live.gs.scene);
+        assert_eq!(replayed.gs.score, live.gs.score);
+        assert_eq!(replayed.high_scores.entries.len(), live.high_scores.entries.len());
+        assert_eq!(replayed.squirrel.screen_region, live.squirrel.screen_region);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(matches!(Replay::read_from(&b"PNG\0\0\0"[..]), Err(ReplayError::NotAReplay)));
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&99_u16.to_le_bytes());
+        assert!(matches!(Replay::read_from(bytes.as_slice()), Err(ReplayError::UnsupportedVersion(99))));
+    }
+
+    #[test]
+    fn refuses_to_play_on_changed_content() {
+        let mut world = World::new(3);
+        let playback = Replay::new(3, None, world.content_hash()).play();
+        assert!(playback.check_content(world.content_hash()).is_ok());
+        world.difficulty.tiers[0].fall_speed += 1.0;
+        assert!(matches!(playback.check_content(world.content_hash()), Err(ReplayError::ContentChanged)));
+    }
+}
@@ -0,0 +1,212 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::char_action::Char_action;
+use crate::game_state::GameState;
+use crate::popup::Popup;
+use crate::spawner::Spawner;
+use crate::world::World;
+
+// Bump this whenever anything in a SaveGame changes shape
+const VERSION: u32 = 9;
+
+// Everything about a run in progress, RNG included, so that picking it back up
+// carries on exactly as if it had never stopped.  The difficulty tiers, level and
+// high scores aren't part of the run, so they come from their own files as usual;
+// a run only picks back up on the same ones it was saved with.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SaveGame {
+    version: u32,
+    // World::content_hash of the difficulty, level and map the run was on
+    pub content_hash: u64,
+    pub squirrel: Char_action,
+    pub falling: Spawner,
+    pub gs: GameState,
+    pub popups: Vec<Popup>,
+    // World::clock, so the scenery carries on from where it was
+    pub clock: f32,
+}
+
+// Just enough of a SaveGame to check the version before reading the rest
+#[derive(Deserialize)]
+struct VersionOnly {
+    version: u32,
+}
+
+#[derive(Debug)]
+pub enum SaveGameError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    Write(ron::Error),
+    UnsupportedVersion(u32),
+    // the level, difficulty or map files differ from the ones it was saved with
+    ContentChanged,
+    // on a wave the level doesn't have
+    NoSuchWave { wave: usize, waves: usize },
+}
+
+impl fmt::Display for SaveGameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveGameError::Io(e) => write!(f, "couldn't read or write saved game: {}", e),
+            SaveGameError::Parse(e) => write!(f, "couldn't parse saved game: {}", e),
+            SaveGameError::Write(e) => write!(f, "couldn't write saved game: {}", e),
+            SaveGameError::UnsupportedVersion(v) => {
+                write!(f, "saved game version {} is not supported (expected {})", v, VERSION)
+            }
+            SaveGameError::ContentChanged => {
+                write!(f, "the level, difficulty or map files have changed since the game was saved")
+            }
+            SaveGameError::NoSuchWave { wave, waves } => {
+                write!(f, "saved game is on wave {} but the level only has {}", wave + 1, waves)
+            }
+        }
+    }
+}
+
+impl From<std::io::Error> for SaveGameError {
+    fn from(e: std::io::Error) -> Self {
+        SaveGameError::Io(e)
+    }
+}
+
+impl SaveGame {
+    // Next to the high scores, e.g. ~/.local/share/nut-wars/savegame.ron on Linux
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("nut-wars").join("savegame.ron"))
+    }
+
+    pub fn of(world: &World) -> SaveGame {
+        SaveGame {
+            version: VERSION,
+            content_hash: world.content_hash(),
+            squirrel: world.squirrel.clone(),
+            falling: world.falling.clone(),
+            gs: world.gs.clone(),
+            popups: world.popups.clone(),
+            clock: world.clock,
+        }
+    }
+
+    // Put the run back into a world, as long as it's playing the same thing.
+    // If not, the world is left alone.
+    pub fn restore(self, world: &mut World) -> Result<(), SaveGameError> {
+        if self.content_hash != world.content_hash() {
+            return Err(SaveGameError::ContentChanged);
+        }
+        let waves = world.level.as_ref().map_or(0, |level| level.waves.len());
+        if world.level.is_some() && self.gs.wave >= waves {
+            return Err(SaveGameError::NoSuchWave { wave: self.gs.wave, waves });
+        }
+        world.squirrel = self.squirrel;
+        world.falling = self.falling;
+        world.gs = self.gs;
+        world.popups = self.popups;
+        world.clock = self.clock;
+        world.events.clear();
+        Ok(())
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SaveGameError> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, self.to_ron()?)?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<SaveGame, SaveGameError> {
+        let text = std::fs::read_to_string(path)?;
+        SaveGame::from_ron(&text)
+    }
+
+    pub fn from_ron(text: &str) -> Result<SaveGame, SaveGameError> {
+        let VersionOnly { version } = ron::from_str(text).map_err(SaveGameError::Parse)?;
+        if version != VERSION {
+            return Err(SaveGameError::UnsupportedVersion(version));
+        }
+        ron::from_str(text).map_err(SaveGameError::Parse)
+    }
+
+    pub fn to_ron(&self) -> Result<String, SaveGameError> {
+        ron::to_string(self).map_err(SaveGameError::Write)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::InputFrame;
+    use crate::level::Level;
+    use crate::scene::Scene;
+
+    // Walk back and forth so the squirrel, acorns and RNG all have something going on
+    fn input(i: usize) -> InputFrame {
+        let right = (i / 70) % 2 == 1;
+        InputFrame { left: !right, right, ..Default::default() }
+    }
+
+    #[test]
+    fn save_load_step_matches_stepping_the_original() {
+        let mut original = World::new(77);
+        original.step(&InputFrame { start: true, ..Default::default() });
+        // plenty of lives so the run is still going by the end
+        original.gs.lives = 100;
+        for i in 0..500 {
+            original.step(&input(i));
+        }
+
+        let text = SaveGame::of(&original).to_ron().unwrap();
+        let mut resumed = World::new(1);
+        SaveGame::from_ron(&text).unwrap().restore(&mut resumed).unwrap();
+        assert_eq!(SaveGame::of(&resumed).to_ron().unwrap(), text);
+        assert_eq!(resumed.clock, original.clock);
+
+        for i in 500..1500 {
+            original.step(&input(i));
+            resumed.step(&input(i));
+        }
+        assert_eq!(original.gs.scene, Scene::Playing);
+        assert_eq!(resumed.clock, original.clock);
+        assert_eq!(SaveGame::of(&resumed).to_ron().unwrap(), SaveGame::of(&original).to_ron().unwrap());
+    }
+
+    #[test]
+    fn only_resumes_on_the_same_content() {
+        // always called the same thing, with this many waves
+        let level = |count: usize| {
+            let wave = "(name: \"W\", fall_speed: 100.0, spawn_interval: 1.0, max_live: 1, goal: Catch(5))";
+            Level::from_ron(&format!("(name: \"Same name\", waves: [{}])", vec![wave; count].join(","))).unwrap()
+        };
+        let mut original = World::new(5);
+        original.level = Some(level(3));
+        original.step(&InputFrame { start: true, ..Default::default() });
+        original.gs.wave = 2;
+        let save = SaveGame::of(&original);
+
+        // same name, but a wave shorter
+        let mut edited = World::new(5);
+        edited.level = Some(level(2));
+        assert!(matches!(save.clone().restore(&mut edited), Err(SaveGameError::ContentChanged)));
+        assert_eq!(edited.gs.scene, Scene::Title);
+
+        // a hand-edited save can still point past the end
+        let mut same = World::new(5);
+        same.level = Some(level(3));
+        let mut past_the_end = save.clone();
+        past_the_end.gs.wave = 3;
+        let err = past_the_end.restore(&mut same).unwrap_err();
+        assert_eq!(err.to_string(), "saved game is on wave 4 but the level only has 3");
+        assert!(save.restore(&mut same).is_ok());
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        let text = SaveGame::of(&World::new(0)).to_ron().unwrap().replacen(&format!("version:{}", VERSION), "version:99", 1);
+        assert!(matches!(SaveGame::from_ron(&text), Err(SaveGameError::UnsupportedVersion(99))));
+        assert!(matches!(SaveGame::from_ron("not a save"), Err(SaveGameError::Parse(_))));
+    }
+}
@@ -0,0 +1,177 @@
+use serde::{Deserialize, Serialize};
+
+use crate::input::InputFrame;
+use crate::level::Goal;
+use crate::world::{combo_multiplier, GameEvent, World};
+
+// Which screen the game is on.  The flow is
+// Title -> Playing <-> Paused -> (EnterName ->) GameOver -> Title,
+// and Playing can also end by itself.  EnterName only shows up when the
+// score made the high score table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Scene {
+    Title,
+    Playing,
+    Paused,
+    EnterName,
+    GameOver,
+}
+
+// What a scene wants on screen this frame
+pub struct SceneView {
+    // draw the squirrel and whatever is falling?
+    pub show_world: bool,
+    pub text: String,
+}
+
+impl Scene {
+    // The update hook: one fixed step of whatever this scene does
+    pub fn update(self, world: &mut World, input: &InputFrame) {
+        match self {
+            Scene::Title => update_title(world, input),
+            Scene::Playing => update_playing(world, input),
+            Scene::Paused => update_paused(world, input),
+            Scene::EnterName => update_enter_name(world, input),
+            Scene::GameOver => update_game_over(world, input),
+        }
+    }
+
+    // The render hook: what run() should draw for this scene
+    pub fn render(self, world: &World) -> SceneView {
+        match self {
+            Scene::Title => render_title(world),
+            Scene::Playing => render_playing(world),
+            Scene::Paused => render_paused(world),
+            Scene::EnterName => render_enter_name(world),
+            Scene::GameOver => render_game_over(world),
+        }
+    }
+}
+
+fn update_title(world: &mut World, input: &InputFrame) {
+    if input.start {
+        world.start_run();
+    }
+}
+
+fn update_playing(world: &mut World, input: &InputFrame) {
+    if input.pause {
+        world.gs.scene = Scene::Paused;
+        world.events.push(GameEvent::Paused);
+        return;
+    }
+    world.play_step(input);
+}
+
+fn update_paused(world: &mut World, input: &InputFrame) {
+    if input.pause {
+        world.gs.scene = Scene::Playing;
+    } else if input.start {
+        // giving up ends the run
+        world.end_run();
+    }
+}
+
+// The letters themselves come in through World::type_char
+fn update_enter_name(world: &mut World, input: &InputFrame) {
+    if input.start {
+        world.submit_name();
+    }
+}
+
+fn update_game_over(world: &mut World, input: &InputFrame) {
+    if input.start {
+        world.gs.scene = Scene::Title;
+    }
+}
+
+fn render_title(_world: &World) -> SceneView {
+    SceneView {
+        show_world: false,
+        text: "NUT WARS\nPress Enter to start".to_string(),
+    }
+}
+
+fn render_playing(world: &World) -> SceneView {
+    SceneView {
+        show_world: true,
+        text: hud_text(world),
+    }
+}
+
+fn render_paused(world: &World) -> SceneView {
+    SceneView {
+        show_world: true,
+        text: format!("{}\nPaused\nP: resume  Enter: quit", hud_text(world)),
+    }
+}
+
+fn render_enter_name(world: &World) -> SceneView {
+    SceneView {
+        show_world: false,
+        text: format!(
+            "{}New high score: {}!\nName: {}_\nPress Enter to save",
+            cleared_text(world), world.gs.score, world.gs.chars_typed
+        ),
+    }
+}
+
+fn render_game_over(world: &World) -> SceneView {
+    let mut text = match cleared_text(world) {
+        "" => format!("Game over!   Score: {}\n", world.gs.score),
+        cleared => format!("{}Score: {}\n", cleared, world.gs.score),
+    };
+    for (i, entry) in world.high_scores.entries.iter().enumerate() {
+        // point out the run that just went in
+        let marker = if world.last_rank == Some(i) { ">" } else { " " };
+        let seconds = entry.seconds as u32;
+        text.push_str(&format!(
+            "{}{:>2}. {:<12} {:>5}  {}  {}:{:02}\n",
+            marker, i + 1, entry.name, entry.score, entry.date, seconds / 60, seconds % 60
+        ));
+    }
+    text.push_str("Press Enter");
+    SceneView {
+        show_world: false,
+        text,
+    }
+}
+
+// The score line shown while a run is going
+fn hud_text(world: &World) -> String {
+    let mut text = format!("Score: {}   Lives: {}", world.gs.score, world.gs.lives);
+    if world.gs.combo > 1 {
+        text.push_str(&format!("   Combo: {} (x{})", world.gs.combo, combo_multiplier(world.gs.combo)));
+    }
+    // one timer per running power-up, e.g. "Magnet 6.5s"
+    for (power_up, left) in world.gs.power_ups.running() {
+        text.push_str(&format!("   {} {:.1}s", power_up.name(), left));
+    }
+    if let Some(wave) = world.level.as_ref().and_then(|level| level.waves.get(world.gs.wave)) {
+        let count = world.level.as_ref().map_or(0, |level| level.waves.len());
+        text.push_str(&format!("\nWave {}/{}: {}", world.gs.wave + 1, count, wave.name));
+        match wave.goal {
+            Goal::Catch(acorns) => text.push_str(&format!("   Caught {}/{}", world.gs.wave_caught, acorns)),
+            Goal::Score(points) => {
+                text.push_str(&format!("   Points {}/{}", world.gs.score - world.gs.wave_start_score, points))
+            }
+            Goal::Survive => text.push_str("   Hang in there!"),
+        }
+        if let Some(seconds) = wave.seconds {
+            text.push_str(&format!("   {:.0}s left", (seconds - world.gs.wave_time).max(0.0).ceil()));
+        }
+    }
+    if world.gs.stunned > 0.0 {
+        text.push_str("\nOuch! Seeing stars...");
+    }
+    text
+}
+
+// Said first at the end of a run that made it through the whole level
+fn cleared_text(world: &World) -> &'static str {
+    if world.gs.cleared {
+        "Level cleared!   "
+    } else {
+        ""
+    }
+}
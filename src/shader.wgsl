@@ -1,44 +1,3 @@
-// Where is the nth vertex in normalized device coordinates?
-var<private> VERTICES_bg:array<vec4<f32>,6> = array<vec4<f32>,6>(
-    // In WGPU, the bottom left corner is -1,-1 and the top right is 1,1.
-    vec4<f32>(-1., -1., 0., 1.),
-    vec4<f32>(1., -1., 0., 1.),
-    vec4<f32>(-1., 1., 0., 1.),
-    vec4<f32>(-1., 1., 0., 1.),
-    vec4<f32>(1., -1., 0., 1.),
-    vec4<f32>(1., 1., 0., 1.)
-);
-
-// How does each vertex map onto the texture's corners?
-var<private> TEX_COORDS_bg:array<vec2<f32>,6> = array<vec2<f32>,6>(
-    // Texture coordinates are a bit different---they go from 0,0 at the top left to 1,1 at the bottom right,
-    // but if they are outside that bound they may clamp, or repeat the texture, or something else
-    // depending on the sampler.
-    vec2<f32>(0., 1.),
-    vec2<f32>(1., 1.),
-    vec2<f32>(0., 0.),
-    vec2<f32>(0., 0.),
-    vec2<f32>(1., 1.),
-    vec2<f32>(1., 0.)
-);
-
-// Now we're outputting more than just a position,
-// so we'll define a struct
-struct VertexOutput_bg {
-    @builtin(position) clip_position: vec4<f32>,
-    @location(0) tex_coords: vec2<f32>,
-}
-
-// vs_main now produces an instance of that struct...
-@vertex
-fn vs_main_bg(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
-    // We'll just look up the vertex data in those constant arrays
-    return VertexOutput_bg(
-        VERTICES_bg[in_vertex_index],
-        TEX_COORDS_bg[in_vertex_index]
-    );
-}
-
 // A square made of two rectangles. This makes our vertex shader
 // code simpler since we can look up the corners by number.
 var<private> VERTICES:array<vec2<f32>,6> = array<vec2<f32>,6>(
@@ -60,7 +19,20 @@ struct Camera {
 // GPUSprite, from before
 struct GPUSprite {
     to_rect:vec4<f32>,
-    from_rect:vec4<f32>
+    from_rect:vec4<f32>,
+    // multiplied into the texture's color, alpha included
+    tint:vec4<f32>,
+    // rotation and scale happen around this point, in fractions of the sprite
+    pivot:vec2<f32>,
+    // radians counterclockwise
+    rotation:f32,
+    scale:f32,
+    // bit 1 mirrors the texture left to right, bit 2 top to bottom
+    flags:u32,
+    // keeps the Rust struct and this one the same size
+    _pad0:u32,
+    _pad1:u32,
+    _pad2:u32
 }
 
 // One binding for the camera...
@@ -74,6 +46,7 @@ var<storage, read> sprites: array<GPUSprite>;
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
+    @location(1) tint: vec4<f32>,
 }
 
 @vertex
@@ -89,13 +62,27 @@ fn vs_main(@builtin(vertex_index) in_vertex_index: u32,
     let tex_size:vec2<f32> = sprites[sprite_index].from_rect.zw;
     // Which corner of the square we need to draw now (in_vertex_index is in 0..6)
     let which_vtx:vec2<f32> = VERTICES[in_vertex_index];
-    // Which corner of the UV square we need to draw (UV coordinates are flipped in Y)
-    let which_uv: vec2<f32> = vec2(VERTICES[in_vertex_index].x, 1.0 - VERTICES[in_vertex_index].y);
+    // Which corner of the UV square we need to draw (UV coordinates are flipped in Y).
+    // Flipping swaps which side of the texture goes on which side of the sprite,
+    // so the sprite itself stays put.
+    var which_uv: vec2<f32> = vec2(which_vtx.x, 1.0 - which_vtx.y);
+    let flags:u32 = sprites[sprite_index].flags;
+    if (flags & 1u) != 0u { which_uv.x = 1.0 - which_uv.x; }
+    if (flags & 2u) != 0u { which_uv.y = 1.0 - which_uv.y; }
+    // Where this corner is relative to the pivot, scaled and then turned around it
+    let pivot:vec2<f32> = sprites[sprite_index].pivot * size;
+    let angle:f32 = sprites[sprite_index].rotation;
+    let from_pivot:vec2<f32> = (which_vtx*size - pivot) * sprites[sprite_index].scale;
+    let turned:vec2<f32> = vec2(
+        from_pivot.x * cos(angle) - from_pivot.y * sin(angle),
+        from_pivot.x * sin(angle) + from_pivot.y * cos(angle)
+    );
     return VertexOutput(
-        // Offset corner by size * which_vtx to get the right corner, then do camera stuff. Dividing screen size by 2 and the last subtraction are to deal with the NDC coordinate space, which goes from -1 to 1 in WGPU.
-        ((corner + vec4(which_vtx*size,0.,0.) - vec4(camera.screen_pos,0.,0.)) / vec4(camera.screen_size/2., 1.0, 1.0)) - vec4(1.0, 1.0, 0.0, 0.0),
+        // Offset corner by that to get the right corner, then do camera stuff. Dividing screen size by 2 and the last subtraction are to deal with the NDC coordinate space, which goes from -1 to 1 in WGPU.
+        ((corner + vec4(pivot + turned,0.,0.) - vec4(camera.screen_pos,0.,0.)) / vec4(camera.screen_size/2., 1.0, 1.0)) - vec4(1.0, 1.0, 0.0, 0.0),
         // Offset texture corner by tex_size * which_uv to get the right corner
-        tex_corner + which_uv*tex_size
+        tex_corner + which_uv*tex_size,
+        sprites[sprite_index].tint
     );
 }
 
@@ -112,25 +99,66 @@ var s_diffuse: sampler;
 @fragment
 fn fs_main(in:VertexOutput) -> @location(0) vec4<f32> {
     // And we use the tex coords from the vertex output to sample from the texture.
-    let color:vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords);
-    // This is new: if the alpha value of the color is very low, don't draw any fragment here.
-    // This is like "cutout" transparency.
-    if color.w < 0.2 { discard; }
+    // The pipeline blends by alpha, so see-through texels and tints just work.
+    let color:vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords) * in.tint;
+    // Nothing to draw here at all
+    if color.w <= 0.0 { discard; }
     return color;
 }
 
-// Now our fragment shader needs two "global" inputs to be bound:
-// A texture...
-@group(0) @binding(0)
-var t_diffuse_bg: texture_2d<f32>;
-// And a sampler.
-@group(0) @binding(1)
-var s_diffuse_bg: sampler;
-// Both are in the same binding group here since they go together naturally.
+// Backgrounds are a single quad covering the whole screen, already in
+// normalized device coordinates, so they don't need the camera.
+var<private> VERTICES_bg:array<vec4<f32>,6> = array<vec4<f32>,6>(
+    // In WGPU, the bottom left corner is -1,-1 and the top right is 1,1.
+    vec4<f32>(-1., -1., 0., 1.),
+    vec4<f32>(1., -1., 0., 1.),
+    vec4<f32>(-1., 1., 0., 1.),
+    vec4<f32>(-1., 1., 0., 1.),
+    vec4<f32>(1., -1., 0., 1.),
+    vec4<f32>(1., 1., 0., 1.)
+);
+
+// How does each vertex map onto the texture's corners?
+var<private> TEX_COORDS_bg:array<vec2<f32>,6> = array<vec2<f32>,6>(
+    // Texture coordinates are a bit different---they go from 0,0 at the top left to 1,1 at the bottom right,
+    // but if they are outside that bound they may clamp, or repeat the texture, or something else
+    // depending on the sampler.
+    vec2<f32>(0., 1.),
+    vec2<f32>(1., 1.),
+    vec2<f32>(0., 0.),
+    vec2<f32>(0., 0.),
+    vec2<f32>(1., 1.),
+    vec2<f32>(1., 0.)
+);
+
+// GPUBackground: which part of the texture the screen shows
+struct Background {
+    uv_offset: vec2<f32>,
+    uv_scale: vec2<f32>
+}
+
+// Binding 2, since the camera and sprites already have 0 and 1 in group 0.
+// The texture and sampler are the same group 1 ones the sprites use.
+@group(0) @binding(2)
+var<uniform> background: Background;
+
+struct VertexOutput_bg {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+}
+
+@vertex
+fn vs_main_bg(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput_bg {
+    // We'll just look up the vertex data in those constant arrays,
+    // then move and stretch the UVs to show the right part of the texture
+    return VertexOutput_bg(
+        VERTICES_bg[in_vertex_index],
+        background.uv_offset + TEX_COORDS_bg[in_vertex_index] * background.uv_scale
+    );
+}
 
-// Our fragment shader takes an interpolated `VertexOutput` as input now
 @fragment
-fn fs_main_bg(in:VertexOutput) -> @location(0) vec4<f32> {
-    // And we use the tex coords from the vertex output to sample from the texture.
-    return textureSample(t_diffuse_bg, s_diffuse_bg, in.tex_coords);
+fn fs_main_bg(in:VertexOutput_bg) -> @location(0) vec4<f32> {
+    // The pipeline blends by alpha, so a background can have holes to see the ones behind it
+    return textureSample(t_diffuse, s_diffuse, in.tex_coords);
 }
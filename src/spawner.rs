@@ -0,0 +1,108 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::bounds::WorldSize;
+use crate::char_action::Char_action;
+use crate::powerup::PowerUp;
+
+// The different things that can fall from the trees
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Kind {
+    // catch these
+    Acorn,
+    // dodge these
+    Hazard,
+    // catch these too, for a while of something good
+    PowerUp(PowerUp),
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Falling {
+    pub kind: Kind,
+    pub body: Char_action,
+}
+
+// Keeps a pool of falling objects topped up.  Every `interval` seconds a copy of
+// one of the templates appears at a random spot in x_range along the top, as
+// long as there are fewer than `max_live` already falling.  Whoever owns the
+// spawner takes objects back out of `objects` when they're caught or missed.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Spawner {
+    pub objects: Vec<Falling>,
+    acorn: Char_action,
+    hazard: Char_action,
+    // one per power-up, in PowerUp::ALL order
+    power_ups: Vec<Char_action>,
+    // seconds between spawns
+    pub interval: f32,
+    pub max_live: usize,
+    // pixels per second for newly spawned objects
+    pub fall_speed: f32,
+    // 0.0 to 1.0, how likely each spawn is a hazard
+    pub hazard_chance: f32,
+    // 0.0 to 1.0, how likely each spawn is a power-up (of any kind)
+    pub power_up_chance: f32,
+    // the leftmost and rightmost x a new object can appear at; past the right
+    // side of the world just means as far right as it fits
+    pub x_range: (f32, f32),
+    // new objects appear at the top of this, all the way on screen
+    pub world_size: WorldSize,
+    // seconds until the next spawn
+    timer: f32,
+}
+
+impl Spawner {
+    pub fn new(
+        acorn: Char_action,
+        hazard: Char_action,
+        power_ups: Vec<Char_action>,
+        interval: f32,
+        max_live: usize,
+        world_size: WorldSize,
+    ) -> Spawner {
+        Spawner {
+            objects: Vec::new(),
+            fall_speed: acorn.speed,
+            acorn,
+            hazard,
+            power_ups,
+            interval,
+            max_live,
+            hazard_chance: 0.0,
+            power_up_chance: 0.0,
+            x_range: (0.0, f32::MAX),
+            world_size,
+            // the first one shows up right away
+            timer: 0.0,
+        }
+    }
+
+    pub fn update(&mut self, dt: f32, rng: &mut impl Rng) {
+        self.timer -= dt;
+        if self.timer <= 0.0 {
+            if self.objects.len() < self.max_live {
+                let roll = rng.gen::<f32>();
+                let kind = if roll < self.hazard_chance {
+                    Kind::Hazard
+                } else if roll < self.hazard_chance + self.power_up_chance {
+                    Kind::PowerUp(PowerUp::ALL[rng.gen_range(0..PowerUp::ALL.len())])
+                } else {
+                    Kind::Acorn
+                };
+                self.spawn(kind, rng);
+            }
+            self.timer += self.interval;
+        }
+    }
+
+    pub fn spawn(&mut self, kind: Kind, rng: &mut impl Rng) {
+        let mut body = match kind {
+            Kind::Acorn => self.acorn.clone(),
+            Kind::Hazard => self.hazard.clone(),
+            Kind::PowerUp(power_up) => self.power_ups[power_up as usize].clone(),
+        };
+        body.speed = self.fall_speed;
+        body.reset_y(self.world_size, self.x_range, rng);
+        self.objects.push(Falling { kind, body });
+    }
+}
@@ -1,14 +1,73 @@
-use std::{borrow::Cow, ops::Range};
+use std::{borrow::Cow, ops::{Range, RangeInclusive}};
 
 use crate::gpus::WGPU;
-// use crate::{USE_STORAGE, WGPU};
 use bytemuck::{Pod, Zeroable};
 
 #[repr(C)]
 #[derive(Clone, Copy, Zeroable, Pod)]
 pub struct GPUSprite {
     pub screen_region: [f32; 4],
+    // Textures with a bunch of sprites are often called "sprite sheets"
     pub sheet_region: [f32; 4],
+    // multiplied into the texture's colors; the last one is opacity
+    pub tint: [f32; 4],
+    // what rotation and scale go around, as a fraction of the sprite: [0.5, 0.5] is the middle
+    pub pivot: [f32; 2],
+    // radians counterclockwise
+    pub rotation: f32,
+    // 1.0 is the size screen_region says
+    pub scale: f32,
+    // FLIP_X and/or FLIP_Y
+    pub flags: u32,
+    // the shader's sprites are 16-byte aligned
+    pub _padding: [u32; 3],
+}
+
+// Draw the sheet region mirrored, without moving or resizing the sprite on screen
+pub const FLIP_X: u32 = 1;
+#[allow(dead_code)]
+pub const FLIP_Y: u32 = 2;
+
+#[allow(dead_code)]
+impl GPUSprite {
+    // Untinted, unturned and unscaled
+    pub fn new(screen_region: [f32; 4], sheet_region: [f32; 4]) -> Self {
+        GPUSprite {
+            screen_region,
+            sheet_region,
+            tint: [1.0; 4],
+            pivot: [0.5, 0.5],
+            rotation: 0.0,
+            scale: 1.0,
+            flags: 0,
+            _padding: [0; 3],
+        }
+    }
+
+    pub fn with_flags(mut self, flags: u32) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    pub fn with_tint(mut self, tint: [f32; 4]) -> Self {
+        self.tint = tint;
+        self
+    }
+
+    pub fn with_rotation(mut self, rotation: f32) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_pivot(mut self, pivot: [f32; 2]) -> Self {
+        self.pivot = pivot;
+        self
+    }
 }
 
 #[repr(C)]
@@ -18,6 +77,58 @@ pub struct GPUCamera {
     pub screen_size: [f32; 2],
 }
 
+// Which part of a background's texture fills the screen, in UVs: the screen's
+// top left corner shows uv_offset and the whole screen is uv_scale across
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod)]
+pub struct GPUBackground {
+    pub uv_offset: [f32; 2],
+    pub uv_scale: [f32; 2],
+}
+
+impl Default for GPUBackground {
+    // the whole texture, once
+    fn default() -> Self {
+        GPUBackground { uv_offset: [0.0, 0.0], uv_scale: [1.0, 1.0] }
+    }
+}
+
+// What a sprite group is part of.  Layers are drawn back to front in this
+// order, whatever order the groups were added in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Layer {
+    Background,
+    Parallax,
+    World,
+    // things that are just for show, over the world
+    Effects,
+    // always on top; text gets drawn after this
+    Hud,
+}
+
+// How a group's sprites combine with what's already been drawn
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Blend {
+    // see-through by alpha, like a sprite normally is
+    Alpha,
+    // adds its color on top, brightening things; good for glows and sparkles
+    Additive,
+}
+
+// The order to draw groups in, given each one's (layer, depth, blend): back to
+// front by layer, then by depth within a layer.  Anything still tied is
+// batched by blend mode (so the pipeline doesn't keep switching), and after
+// that goes in the order it was added.
+fn draw_order(keys: &[(Layer, i32, Blend)]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..keys.len()).collect();
+    // stable, so ties keep their order
+    order.sort_by_key(|&i| keys[i]);
+    order
+}
+
+// Everything drawn from one texture: the sprites, the GPU buffer they're copied
+// into, and the bind groups that point the shader at both
 #[allow(dead_code)]
 struct SpriteGroup {
     tex: wgpu::Texture,
@@ -27,21 +138,54 @@ struct SpriteGroup {
     camera_buffer: wgpu::Buffer,
     tex_bind_group: wgpu::BindGroup,
     sprite_bind_group: wgpu::BindGroup,
+    // hidden groups stay around but don't get drawn
+    visible: bool,
+    layer: Layer,
+    // higher is further in front, among groups on the same layer
+    depth: i32,
+    blend: Blend,
 }
 
+// A texture stretched over the whole screen, behind the sprites on its layer.
+// It ignores the camera; what part of the texture shows is up to its uniform.
+#[allow(dead_code)]
+struct Background {
+    tex: wgpu::Texture,
+    view: GPUBackground,
+    view_buffer: wgpu::Buffer,
+    view_bind_group: wgpu::BindGroup,
+    tex_bind_group: wgpu::BindGroup,
+    layer: Layer,
+}
+
+// Draws any number of sprite groups with one instanced pipeline per blend
+// mode, sorted by layer, and full-screen backgrounds with one more
 pub struct SpriteRenderer {
-    pipeline: wgpu::RenderPipeline,
+    alpha_pipeline: wgpu::RenderPipeline,
+    additive_pipeline: wgpu::RenderPipeline,
+    background_pipeline: wgpu::RenderPipeline,
     sprite_bind_group_layout: wgpu::BindGroupLayout,
     texture_bind_group_layout: wgpu::BindGroupLayout,
+    background_bind_group_layout: wgpu::BindGroupLayout,
     groups: Vec<SpriteGroup>,
+    // indices into groups, in the order they get drawn; redone whenever a
+    // group's layer, depth or blend changes
+    order: Vec<usize>,
+    backgrounds: Vec<Background>,
+    // indices into backgrounds by layer, same as order
+    background_order: Vec<usize>,
 }
 
 impl SpriteRenderer {
     pub(crate) fn new(gpu: &WGPU) -> Self {
+        // Load the shaders from disk.  Remember, shader programs are things we compile for
+        // our GPU so that it can compute vertices and colorize fragments.
         let shader = gpu
             .device
             .create_shader_module(wgpu::ShaderModuleDescriptor {
                 label: None,
+                // Cow is a "copy on write" wrapper that abstracts over owned or borrowed memory.
+                // Here we just need to use it since wgpu wants "some text" to compile a shader from.
                 source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("shader.wgsl"))),
             });
 
@@ -82,21 +226,6 @@ impl SpriteRenderer {
                         },
                     ],
                 });
-        // The camera binding
-        let camera_layout_entry = wgpu::BindGroupLayoutEntry {
-            // This matches the binding in the shader
-            binding: 0,
-            // Available in vertex shader
-            visibility: wgpu::ShaderStages::VERTEX,
-            // It's a buffer
-            ty: wgpu::BindingType::Buffer {
-                ty: wgpu::BufferBindingType::Uniform,
-                has_dynamic_offset: false,
-                min_binding_size: None,
-            },
-            // No count, not a buffer array binding
-            count: None,
-        };
         let sprite_bind_group_layout =
             gpu.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 label: None,
@@ -135,35 +264,24 @@ impl SpriteRenderer {
                     },
                 ],
             });
-        // let sprite_bind_group_layout = if USE_STORAGE {
-        //     gpu.device
-        //         .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-        //             label: None,
-        //             entries: &[
-        //                 camera_layout_entry,
-        //                 wgpu::BindGroupLayoutEntry {
-        //                     // This matches the binding in the shader
-        //                     binding: 1,
-        //                     // Available in vertex shader
-        //                     visibility: wgpu::ShaderStages::VERTEX,
-        //                     // It's a buffer
-        //                     ty: wgpu::BindingType::Buffer {
-        //                         ty: wgpu::BufferBindingType::Storage { read_only: true },
-        //                         has_dynamic_offset: false,
-        //                         min_binding_size: None,
-        //                     },
-        //                     // No count, not a buffer array binding
-        //                     count: None,
-        //                 },
-        //             ],
-        //         })
-        // } else {
-        //     gpu.device
-        //         .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-        //             label: None,
-        //             entries: &[camera_layout_entry],
-        //         })
-        // };
+        let background_bind_group_layout =
+            gpu.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: None,
+                entries: &[
+                    // Which part of the texture to show.  Binding 2 so it doesn't
+                    // clash with the camera and sprites in the shader.
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None
+                        },
+                        count: None,
+                    },
+                ],
+            });
         let pipeline_layout = gpu
             .device
             .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
@@ -171,8 +289,22 @@ impl SpriteRenderer {
                 bind_group_layouts: &[&sprite_bind_group_layout, &texture_bind_group_layout],
                 push_constant_ranges: &[],
             });
-        
-        let pipeline = gpu.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        // Backgrounds share the texture bind group layout with the sprites
+        let pipeline_layout_bg = gpu
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[&background_bind_group_layout, &texture_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        // Our specific "function" is going to be a draw call using our shaders. That's what we
+        // set up here, calling the result a render pipeline.  It's not only what shaders to use,
+        // but also how to interpret streams of vertices (e.g. as separate triangles or as a list of lines),
+        // whether to draw both the fronts and backs of triangles, and how many times to run the pipeline for
+        // things like multisampling antialiasing.
+        // The two only differ in how they blend.
+        let create_pipeline = |blend| gpu.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
         label: None,
         layout: Some(&pipeline_layout),
         vertex: wgpu::VertexState {
@@ -183,152 +315,128 @@ impl SpriteRenderer {
         fragment: Some(wgpu::FragmentState {
             module: &shader,
             entry_point: "fs_main",
-            targets: &[Some(gpu.config.format.into())],
+            targets: &[Some(wgpu::ColorTargetState {
+                format: gpu.config.format,
+                blend: Some(blend),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
         }),
         primitive: wgpu::PrimitiveState::default(),
         depth_stencil: None,
         multisample: wgpu::MultisampleState::default(),
         multiview: None,});
-
-        // let pipeline = gpu
-        //     .device
-        //     .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-        //         label: None,
-        //         layout: Some(&pipeline_layout),
-        //         vertex: wgpu::VertexState {
-        //             module: &shader,
-        //             entry_point: if USE_STORAGE {
-        //                 "vs_storage_main"
-        //             } else {
-        //                 "vs_vbuf_main"
-        //             },
-        //             buffers: if USE_STORAGE {
-        //                 &[]
-        //             } else {
-        //                 &[wgpu::VertexBufferLayout {
-        //                     array_stride: std::mem::size_of::<GPUSprite>() as u64,
-        //                     step_mode: wgpu::VertexStepMode::Instance,
-        //                     attributes: &[
-        //                         wgpu::VertexAttribute {
-        //                             format: wgpu::VertexFormat::Float32x4,
-        //                             offset: 0,
-        //                             shader_location: 0,
-        //                         },
-        //                         wgpu::VertexAttribute {
-        //                             format: wgpu::VertexFormat::Float32x4,
-        //                             offset: std::mem::size_of::<[f32; 4]>() as u64,
-        //                             shader_location: 1,
-        //                         },
-        //                     ],
-        //                 }]
-        //             },
-        //         },
-        //         fragment: Some(wgpu::FragmentState {
-        //             module: &shader,
-        //             entry_point: "fs_main",
-        //             targets: &[Some(gpu.config.format.into())],
-        //         }),
-        //         primitive: wgpu::PrimitiveState::default(),
-        //         depth_stencil: None,
-        //         multisample: wgpu::MultisampleState::default(),
-        //         multiview: None,
-        //     });
+        // blend by alpha, so tints and fades can be see-through
+        let alpha_pipeline = create_pipeline(wgpu::BlendState::ALPHA_BLENDING);
+        // add the color, scaled by alpha so it can still fade out
+        let additive = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::SrcAlpha,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        };
+        let additive_pipeline = create_pipeline(wgpu::BlendState { color: additive, alpha: additive });
+        // Backgrounds are see-through by alpha too, so layers can go on top of each other
+        let background_pipeline = gpu.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&pipeline_layout_bg),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main_bg",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main_bg",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: gpu.config.format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
 
         Self {
-            pipeline,
+            alpha_pipeline,
+            additive_pipeline,
+            background_pipeline,
             sprite_bind_group_layout,
             texture_bind_group_layout,
+            background_bind_group_layout,
             groups: Vec::default(),
+            order: Vec::default(),
+            backgrounds: Vec::default(),
+            background_order: Vec::default(),
         }
     }
+
+    // Returns the new background's index, for set_background_uv; backgrounds
+    // are numbered separately from sprite groups.  It starts out showing the whole texture.  Past the left or right
+    // edge the texture repeats, so a background can scroll sideways forever.
+    pub fn add_background(&mut self, gpu: &WGPU, tex: wgpu::Texture, layer: Layer) -> usize {
+        let sampler = wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::Repeat,
+            ..Default::default()
+        };
+        let tex_bind_group = self.create_texture_bind_group(gpu, &tex, &sampler);
+        let view = GPUBackground::default();
+        let view_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: std::mem::size_of::<GPUBackground>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        gpu.queue.write_buffer(&view_buffer, 0, bytemuck::bytes_of(&view));
+        let view_bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.background_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 2,
+                resource: view_buffer.as_entire_binding(),
+            }],
+        });
+        self.backgrounds.push(Background {
+            tex,
+            view,
+            view_buffer,
+            view_bind_group,
+            tex_bind_group,
+            layer,
+        });
+        // stable, so backgrounds on the same layer go in the order they were added
+        self.background_order = (0..self.backgrounds.len()).collect();
+        self.background_order.sort_by_key(|&i| self.backgrounds[i].layer);
+        self.backgrounds.len() - 1
+    }
+
+    pub fn set_background_uv(&mut self, gpu: &WGPU, which: usize, view: GPUBackground) {
+        let bg = &mut self.backgrounds[which];
+        bg.view = view;
+        gpu.queue.write_buffer(&bg.view_buffer, 0, bytemuck::bytes_of(&bg.view));
+    }
+
+    // Returns the new group's index, which is how the other methods refer to it.
+    // It starts out at depth 0 and alpha blended.
     pub fn add_sprite_group(
         &mut self,
         gpu: &WGPU,
         tex: wgpu::Texture,
         sprites: Vec<GPUSprite>,
         camera: GPUCamera,
+        layer: Layer,
     ) -> usize {
-        let view_sprite = tex.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler_sprite = gpu
-            .device
-            .create_sampler(&wgpu::SamplerDescriptor::default());
-        
-        let tex_bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: None,
-            layout: &self.texture_bind_group_layout,
-            entries: &[
-                // One for the texture, one for the sampler
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&view_sprite),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&sampler_sprite),
-                },
-            ],
-        });
-        let sprite_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor{
-            label: None,
-            size: bytemuck::cast_slice::<_,u8>(&sprites).len() as u64,
-            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false
-        });
-        // let buffer_sprite = gpu.device.create_buffer(&wgpu::BufferDescriptor {
-        //     label: None,
-        //     size: sprites.len() as u64 * std::mem::size_of::<GPUSprite>() as u64,
-        //     usage: if USE_STORAGE {
-        //         wgpu::BufferUsages::STORAGE
-        //     } else {
-        //         wgpu::BufferUsages::VERTEX
-        //     } | wgpu::BufferUsages::COPY_DST,
-        //     mapped_at_creation: false,
-        // });
+        let tex_bind_group = self.create_texture_bind_group(gpu, &tex, &wgpu::SamplerDescriptor::default());
+        // storage buffers can't be empty, so there's always room for at least one
+        let sprite_buffer = create_sprite_buffer(gpu, sprites.len().max(1));
         let camera_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
             label: None,
             size: std::mem::size_of::<GPUCamera>() as u64,
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
             mapped_at_creation: false,
         });
-        let sprite_bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: None,
-            layout: &self.sprite_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: camera_buffer.as_entire_binding()
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: sprite_buffer.as_entire_binding()
-                }
-            ],
-        });
-        // let sprite_bind_group = if USE_STORAGE {
-        //     gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
-        //         label: None,
-        //         layout: &self.sprite_bind_group_layout,
-        //         entries: &[
-        //             wgpu::BindGroupEntry {
-        //                 binding: 0,
-        //                 resource: camera_buffer.as_entire_binding(),
-        //             },
-        //             wgpu::BindGroupEntry {
-        //                 binding: 1,
-        //                 resource: buffer_sprite.as_entire_binding(),
-        //             },
-        //         ],
-        //     })
-        // } else {
-        //     gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
-        //         label: None,
-        //         layout: &self.sprite_bind_group_layout,
-        //         entries: &[wgpu::BindGroupEntry {
-        //             binding: 0,
-        //             resource: camera_buffer.as_entire_binding(),
-        //         }],
-        //     })
-        // };
+        let sprite_bind_group = self.create_sprite_bind_group(gpu, &camera_buffer, &sprite_buffer);
         gpu.queue
             .write_buffer(&sprite_buffer, 0, bytemuck::cast_slice(&sprites));
         gpu.queue
@@ -336,62 +444,76 @@ impl SpriteRenderer {
 
         self.groups.push(SpriteGroup {
             tex,
-            sprite_buffer: sprite_buffer,
+            sprite_buffer,
             sprites,
             tex_bind_group,
             sprite_bind_group,
             camera,
             camera_buffer,
+            visible: true,
+            layer,
+            depth: 0,
+            blend: Blend::Alpha,
         });
+        self.sort();
         self.groups.len() - 1
     }
 
+    // Groups after this one move down an index
+    #[allow(dead_code)]
     pub fn remove_sprite_group(&mut self, which: usize) {
         self.groups.remove(which);
+        self.sort();
+    }
+
+    #[allow(dead_code)]
+    pub fn set_layer(&mut self, which: usize, layer: Layer) {
+        self.groups[which].layer = layer;
+        self.sort();
+    }
+
+    #[allow(dead_code)]
+    pub fn set_depth(&mut self, which: usize, depth: i32) {
+        self.groups[which].depth = depth;
+        self.sort();
+    }
+
+    pub fn set_blend(&mut self, which: usize, blend: Blend) {
+        self.groups[which].blend = blend;
+        self.sort();
+    }
+
+    fn sort(&mut self) {
+        let keys: Vec<_> = self.groups.iter().map(|group| (group.layer, group.depth, group.blend)).collect();
+        self.order = draw_order(&keys);
+    }
+
+    // Make room for exactly len sprites in a group, returning how many it had
+    // before.  New ones start out zeroed (so they draw nothing) until they're
+    // filled in and uploaded.  The GPU buffer only gets replaced when it's too
+    // small, and then with room to spare so it doesn't happen every frame.
+    pub fn resize_sprite_group(&mut self, gpu: &WGPU, which: usize, len: usize) -> usize {
+        let old_len = self.groups[which].sprites.len();
+        self.groups[which].sprites.resize(len, GPUSprite::zeroed());
+        let new_size = (len * std::mem::size_of::<GPUSprite>()) as u64;
+        if new_size > self.groups[which].sprite_buffer.size() {
+            let sprite_buffer = create_sprite_buffer(gpu, len.next_power_of_two());
+            // the bind group points at the old buffer, so it needs remaking too
+            let sprite_bind_group =
+                self.create_sprite_bind_group(gpu, &self.groups[which].camera_buffer, &sprite_buffer);
+            let group = &mut self.groups[which];
+            group.sprite_buffer = sprite_buffer;
+            group.sprite_bind_group = sprite_bind_group;
+            gpu.queue
+                .write_buffer(&group.sprite_buffer, 0, bytemuck::cast_slice(&group.sprites));
+        }
+        old_len
+    }
+
+    pub fn set_visible(&mut self, which: usize, visible: bool) {
+        self.groups[which].visible = visible;
     }
-    // pub fn resize_sprite_group(&mut self, gpu: &WGPU, which: usize, len: usize) -> usize {
-    //     let group = &mut self.groups[which];
-    //     let old_len = group.sprites.len();
-    //     // shrink or grow sprite vec
-    //     group.sprites.resize(len, GPUSprite::zeroed());
-    //     // realloc buffer if needed, remake sprite_bind_group if using storage buffers
-    //     let new_size = len * std::mem::size_of::<GPUSprite>();
-    //     if new_size > group.sprite_buffer.size() as usize {
-    //         group.sprite_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
-    //             label: None,
-    //             size: new_size as u64,
-    //             usage: if USE_STORAGE {
-    //                 wgpu::BufferUsages::STORAGE
-    //             } else {
-    //                 wgpu::BufferUsages::VERTEX
-    //             } | wgpu::BufferUsages::COPY_DST,
-    //             mapped_at_creation: false,
-    //         });
-    //         if USE_STORAGE {
-    //             group.sprite_bind_group =
-    //                 gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
-    //                     label: None,
-    //                     layout: &self.sprite_bind_group_layout,
-    //                     entries: &[
-    //                         wgpu::BindGroupEntry {
-    //                             binding: 0,
-    //                             resource: group.camera_buffer.as_entire_binding(),
-    //                         },
-    //                         wgpu::BindGroupEntry {
-    //                             binding: 1,
-    //                             resource: group.sprite_buffer.as_entire_binding(),
-    //                         },
-    //                     ],
-    //                 });
-    //         };
-    //         gpu.queue.write_buffer(
-    //             &group.sprite_buffer,
-    //             0,
-    //             bytemuck::cast_slice(&group.sprites),
-    //         );
-    //     }
-    //     old_len
-    // }
+
     pub fn set_camera_all(&mut self, gpu: &WGPU, camera: GPUCamera) {
         for sg_index in 0..self.groups.len() {
             self.set_camera(gpu, sg_index, camera);
@@ -403,35 +525,149 @@ impl SpriteRenderer {
         gpu.queue
             .write_buffer(&sg.camera_buffer, 0, bytemuck::bytes_of(&sg.camera));
     }
+
+    // Copy some of a group's sprites over to the GPU after changing them
     pub fn upload_sprites(&mut self, gpu: &WGPU, which: usize, range: Range<usize>) {
         gpu.queue.write_buffer(
             &self.groups[which].sprite_buffer,
-            range.start as u64,
+            // the offset is in bytes, not sprites
+            (range.start * std::mem::size_of::<GPUSprite>()) as u64,
             bytemuck::cast_slice(&self.groups[which].sprites[range]),
         );
     }
+    #[allow(dead_code)]
     pub fn get_sprites(&self, which: usize) -> &[GPUSprite] {
         &self.groups[which].sprites
     }
     pub fn get_sprites_mut(&mut self, which: usize) -> &mut [GPUSprite] {
         &mut self.groups[which].sprites
     }
-    // pub(crate) fn render<'s, 'pass>(&'s self, rpass: &mut wgpu::RenderPass<'pass>)
-    // where
-    //     's: 'pass,
-    // {
-    //     rpass.set_pipeline(&self.pipeline);
-    //     for group in self.groups.iter() {
-    //         if !USE_STORAGE {
-    //             rpass.set_vertex_buffer(0, group.sprite_buffer.slice(..));
-    //         }
-    //         rpass.set_bind_group(0, &group.sprite_bind_group, &[]);
-    //         rpass.set_bind_group(1, &group.tex_bind_group, &[]);
-    //         // draw two triangles per sprite, and sprites-many sprites.
-    //         // this uses instanced drawing, but it would also be okay
-    //         // to draw 6 * sprites.len() vertices and use modular arithmetic
-    //         // to figure out which sprite we're drawing.
-    //         rpass.draw(0..6, 0..(group.sprites.len() as u32));
-    //     }
-    // }
+
+    // Draw the backgrounds and visible groups on these layers, back to front,
+    // with each layer's backgrounds behind its groups.  Anything drawn in the
+    // same pass afterwards (like the text) goes on top of them.
+    pub(crate) fn render<'s, 'pass>(&'s self, rpass: &mut wgpu::RenderPass<'pass>, layers: RangeInclusive<Layer>)
+    where
+        's: 'pass,
+    {
+        let mut current = None;
+        let mut backgrounds = self
+            .background_order
+            .iter()
+            .map(|&i| &self.backgrounds[i])
+            .filter(|bg| layers.contains(&bg.layer))
+            .peekable();
+        let groups = self.order.iter().map(|&i| &self.groups[i]);
+        for group in groups.filter(|group| group.visible && !group.sprites.is_empty() && layers.contains(&group.layer)) {
+            while let Some(bg) = backgrounds.next_if(|bg| bg.layer <= group.layer) {
+                self.render_background(rpass, bg);
+                // the next group has to set its pipeline again
+                current = None;
+            }
+            // only switch pipelines when the blend mode changes
+            if current != Some(group.blend) {
+                rpass.set_pipeline(match group.blend {
+                    Blend::Alpha => &self.alpha_pipeline,
+                    Blend::Additive => &self.additive_pipeline,
+                });
+                current = Some(group.blend);
+            }
+            rpass.set_bind_group(0, &group.sprite_bind_group, &[]);
+            rpass.set_bind_group(1, &group.tex_bind_group, &[]);
+            // draw two triangles per sprite, and sprites-many sprites.
+            // this uses instanced drawing, but it would also be okay
+            // to draw 6 * sprites.len() vertices and use modular arithmetic
+            // to figure out which sprite we're drawing.
+            rpass.draw(0..6, 0..(group.sprites.len() as u32));
+        }
+        // and any on layers in front of the last group
+        for bg in backgrounds {
+            self.render_background(rpass, bg);
+        }
+    }
+
+    fn render_background<'s, 'pass>(&'s self, rpass: &mut wgpu::RenderPass<'pass>, bg: &'s Background)
+    where
+        's: 'pass,
+    {
+        rpass.set_pipeline(&self.background_pipeline);
+        rpass.set_bind_group(0, &bg.view_bind_group, &[]);
+        rpass.set_bind_group(1, &bg.tex_bind_group, &[]);
+        // one quad, two triangles, covering the screen
+        rpass.draw(0..6, 0..1);
+    }
+
+    fn create_texture_bind_group(
+        &self,
+        gpu: &WGPU,
+        tex: &wgpu::Texture,
+        sampler: &wgpu::SamplerDescriptor,
+    ) -> wgpu::BindGroup {
+        let view = tex.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = gpu.device.create_sampler(sampler);
+        gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.texture_bind_group_layout,
+            entries: &[
+                // One for the texture, one for the sampler
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        })
+    }
+
+    fn create_sprite_bind_group(
+        &self,
+        gpu: &WGPU,
+        camera_buffer: &wgpu::Buffer,
+        sprite_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.sprite_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera_buffer.as_entire_binding()
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: sprite_buffer.as_entire_binding()
+                }
+            ],
+        })
+    }
+}
+
+fn create_sprite_buffer(gpu: &WGPU, capacity: usize) -> wgpu::Buffer {
+    gpu.device.create_buffer(&wgpu::BufferDescriptor{
+        label: None,
+        size: (capacity * std::mem::size_of::<GPUSprite>()) as u64,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_draw_by_layer_then_depth_then_blend() {
+        let order = draw_order(&[
+            (Layer::Hud, 0, Blend::Alpha),
+            (Layer::World, 0, Blend::Additive),
+            (Layer::Background, 0, Blend::Alpha),
+            (Layer::World, 0, Blend::Alpha),
+            (Layer::World, -1, Blend::Additive),
+            (Layer::World, 0, Blend::Alpha),
+        ]);
+        assert_eq!(order, vec![2, 4, 3, 5, 1, 0]);
+    }
 }
@@ -0,0 +1,210 @@
+use std::fmt;
+use std::path::Path;
+
+use crate::collision::Rect;
+
+const BUILT_IN: &str = include_str!("../content/levels/forest.csv");
+
+// tiles are square, this many pixels on a side both on screen and in tileset.png
+pub const TILE_SIZE: f32 = 32.0;
+// tileset.png is a single row of tiles; tile n is the nth one along, counting from 1
+pub const TILESET_COLUMNS: u16 = 4;
+// which tiles can be stood on; the rest are just scenery
+const SOLID: [u16; 3] = [1, 2, 3];
+
+// A level laid out on a grid of tiles, read from a CSV file with one number per
+// tile and the top row first.  Lines starting with # are comments.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tilemap {
+    pub width: usize,
+    pub height: usize,
+    // row by row from the top, like the file; 0 is an empty spot
+    pub tiles: Vec<u16>,
+}
+
+#[derive(Debug)]
+pub enum TilemapError {
+    Io(std::io::Error),
+    // line numbers count from 1 and include comments, so they match an editor
+    NotANumber { line: usize, column: usize, text: String },
+    UnknownTile { line: usize, column: usize, id: u16 },
+    Ragged { line: usize, expected: usize, found: usize },
+    Empty,
+}
+
+impl fmt::Display for TilemapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TilemapError::Io(e) => write!(f, "couldn't read level: {}", e),
+            TilemapError::NotANumber { line, column, text } => {
+                write!(f, "bad level: line {}, tile {}: {:?} isn't a tile number", line, column, text)
+            }
+            TilemapError::UnknownTile { line, column, id } => {
+                write!(f, "bad level: line {}, tile {}: there's no tile {} in the tileset", line, column, id)
+            }
+            TilemapError::Ragged { line, expected, found } => {
+                write!(f, "bad level: line {} has {} tiles but the first row has {}", line, found, expected)
+            }
+            TilemapError::Empty => write!(f, "bad level: there are no tiles in it"),
+        }
+    }
+}
+
+impl From<std::io::Error> for TilemapError {
+    fn from(e: std::io::Error) -> Self {
+        TilemapError::Io(e)
+    }
+}
+
+impl Default for Tilemap {
+    fn default() -> Self {
+        Tilemap::from_csv(BUILT_IN).expect("built-in forest.csv is broken")
+    }
+}
+
+impl Tilemap {
+    pub fn load(path: impl AsRef<Path>) -> Result<Tilemap, TilemapError> {
+        let text = std::fs::read_to_string(path)?;
+        Tilemap::from_csv(&text)
+    }
+
+    pub fn from_csv(text: &str) -> Result<Tilemap, TilemapError> {
+        let mut width = 0;
+        let mut height = 0;
+        let mut tiles = vec![];
+        for (i, line) in text.lines().enumerate() {
+            let line_number = i + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let row_start = tiles.len();
+            for (j, cell) in line.split(',').enumerate() {
+                let cell = cell.trim();
+                let id: u16 = cell.parse().map_err(|_| TilemapError::NotANumber {
+                    line: line_number,
+                    column: j + 1,
+                    text: cell.to_string(),
+                })?;
+                if id > TILESET_COLUMNS {
+                    return Err(TilemapError::UnknownTile { line: line_number, column: j + 1, id });
+                }
+                tiles.push(id);
+            }
+            let found = tiles.len() - row_start;
+            if height == 0 {
+                width = found;
+            } else if found != width {
+                return Err(TilemapError::Ragged { line: line_number, expected: width, found });
+            }
+            height += 1;
+        }
+        if tiles.is_empty() {
+            return Err(TilemapError::Empty);
+        }
+        Ok(Tilemap { width, height, tiles })
+    }
+
+    // row 0 is the top row
+    pub fn tile(&self, column: usize, row: usize) -> u16 {
+        self.tiles[row * self.width + column]
+    }
+
+    // Where a tile is on screen; the bottom row sits on y = 0
+    pub fn tile_rect(&self, column: usize, row: usize) -> Rect {
+        Rect::new(
+            column as f32 * TILE_SIZE,
+            (self.height - 1 - row) as f32 * TILE_SIZE,
+            TILE_SIZE,
+            TILE_SIZE,
+        )
+    }
+
+    pub fn is_solid(id: u16) -> bool {
+        SOLID.contains(&id)
+    }
+
+    // The top of the highest solid tile under feet that's no higher than from_y
+    // (where the feet were before they started moving).  Only looking below
+    // from_y is what lets the squirrel jump up through a branch and land on it.
+    pub fn ground_under(&self, feet: &Rect, from_y: f32) -> Option<f32> {
+        let first = (feet.left() / TILE_SIZE).floor().max(0.0) as usize;
+        // a tile that only touches the right edge isn't under us
+        let last = ((feet.right() / TILE_SIZE).ceil() as usize).min(self.width);
+        let mut ground: Option<f32> = None;
+        for row in 0..self.height {
+            for column in first..last {
+                if !Tilemap::is_solid(self.tile(column, row)) {
+                    continue;
+                }
+                let top = self.tile_rect(column, row).top();
+                if top <= from_y && ground.is_none_or(|g| top > g) {
+                    ground = Some(top);
+                }
+            }
+        }
+        ground
+    }
+
+    // (screen_region, sheet_region) for every tile that isn't empty, ready to
+    // be drawn from tileset.png like any other sprite
+    pub fn sprites(&self) -> Vec<([f32; 4], [f32; 4])> {
+        let mut sprites = vec![];
+        for row in 0..self.height {
+            for column in 0..self.width {
+                let id = self.tile(column, row);
+                if id == 0 {
+                    continue;
+                }
+                let Rect { x, y, w, h } = self.tile_rect(column, row);
+                let sheet_w = 1.0 / TILESET_COLUMNS as f32;
+                sprites.push(([x, y, w, h], [(id - 1) as f32 * sheet_w, 0.0, sheet_w, 1.0]));
+            }
+        }
+        sprites
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEDGE: &str = "# a ledge over the ground\n0,0,0\n0,3,0\n\n1,1,1\n";
+
+    #[test]
+    fn reads_rows_from_the_top() {
+        let map = Tilemap::from_csv(LEDGE).unwrap();
+        assert_eq!((map.width, map.height), (3, 3));
+        assert_eq!(map.tile(1, 1), 3);
+        assert_eq!(map.tile_rect(1, 1), Rect::new(32.0, 32.0, 32.0, 32.0));
+        assert_eq!(map.tile_rect(0, 2), Rect::new(0.0, 0.0, 32.0, 32.0));
+        // the ground and the ledge, but not the empty spots
+        assert_eq!(map.sprites().len(), 4);
+        assert_eq!(map.sprites()[0], ([32.0, 32.0, 32.0, 32.0], [0.5, 0.0, 0.25, 1.0]));
+    }
+
+    #[test]
+    fn mistakes_say_where_they_are() {
+        let err = Tilemap::from_csv("0,0\n# comment\n0,x\n").unwrap_err();
+        assert!(matches!(err, TilemapError::NotANumber { line: 3, column: 2, .. }), "{}", err);
+        let err = Tilemap::from_csv("0,0\n0,0,0\n").unwrap_err();
+        assert!(matches!(err, TilemapError::Ragged { line: 2, expected: 2, found: 3 }), "{}", err);
+        let err = Tilemap::from_csv("0,9\n").unwrap_err();
+        assert!(matches!(err, TilemapError::UnknownTile { line: 1, column: 2, id: 9 }), "{}", err);
+        assert!(matches!(Tilemap::from_csv("# nothing\n"), Err(TilemapError::Empty)));
+        // the built-in level at least has to load
+        Tilemap::default();
+    }
+
+    #[test]
+    fn ground_is_the_highest_tile_below() {
+        let map = Tilemap::from_csv(LEDGE).unwrap();
+        let over_ledge = Rect::new(40.0, 100.0, 10.0, 10.0);
+        assert_eq!(map.ground_under(&over_ledge, 100.0), Some(64.0));
+        // coming up from underneath, the ledge doesn't count yet
+        assert_eq!(map.ground_under(&over_ledge, 40.0), Some(32.0));
+        // and off to the side there's only the ground
+        assert_eq!(map.ground_under(&Rect::new(0.0, 100.0, 32.0, 10.0), 100.0), Some(32.0));
+        assert_eq!(map.ground_under(&Rect::new(500.0, 100.0, 10.0, 10.0), 100.0), None);
+    }
+}
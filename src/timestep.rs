@@ -0,0 +1,70 @@
+use std::time::Instant;
+
+// The simulation always advances in steps of this many seconds, no matter how
+// fast the event loop spins.
+pub const DT: f32 = 1.0 / 60.0;
+
+// If we fall further behind than this (a breakpoint, the window being dragged...)
+// we drop the extra time instead of trying to catch up all at once.
+const MAX_FRAME_TIME: f32 = 0.25;
+
+// A classic accumulator: real time goes in, whole fixed steps come out,
+// and whatever is left over tells the renderer how far between steps we are.
+pub struct FixedTimestep {
+    accumulator: f32,
+    last: Instant,
+}
+
+impl Default for FixedTimestep {
+    fn default() -> Self {
+        FixedTimestep::new()
+    }
+}
+
+impl FixedTimestep {
+    pub fn new() -> FixedTimestep {
+        FixedTimestep {
+            accumulator: 0.0,
+            last: Instant::now(),
+        }
+    }
+
+    // How many steps should we run for the time that passed since the last call?
+    pub fn advance(&mut self) -> usize {
+        let now = Instant::now();
+        let elapsed = (now - self.last).as_secs_f32();
+        self.last = now;
+        self.advance_by(elapsed)
+    }
+
+    pub fn advance_by(&mut self, elapsed: f32) -> usize {
+        self.accumulator += elapsed.min(MAX_FRAME_TIME);
+        let mut steps = 0;
+        while self.accumulator >= DT {
+            self.accumulator -= DT;
+            steps += 1;
+        }
+        steps
+    }
+
+    // 0.0 means "exactly at the last step", 1.0 would be a whole step later
+    pub fn alpha(&self) -> f32 {
+        self.accumulator / DT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refresh_rate_does_not_change_step_count() {
+        let mut at_60 = FixedTimestep::new();
+        let mut at_144 = FixedTimestep::new();
+        let steps_60: usize = (0..60).map(|_| at_60.advance_by(1.0 / 60.0)).sum();
+        let steps_144: usize = (0..144).map(|_| at_144.advance_by(1.0 / 144.0)).sum();
+        // Float rounding can leave the last step sitting in the accumulator
+        assert!((steps_60 as i32 - 60).abs() <= 1);
+        assert!((steps_144 as i32 - 60).abs() <= 1);
+    }
+}
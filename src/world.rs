@@ -0,0 +1,1155 @@
+use crate::animation::Animation;
+use crate::bounds::WorldSize;
+use crate::broadphase::SpatialHash;
+use crate::char_action::Char_action;
+use crate::collision::{Hitbox, Rect};
+use crate::difficulty::Difficulty;
+use crate::fade::{self, Fade};
+use crate::game_state::{self, GameState, START_LIVES};
+use crate::highscores::{self, Entry, HighScores, MAX_NAME_LEN};
+use crate::input::InputFrame;
+use crate::level::{Goal, Level};
+use crate::popup::{self, Popup};
+use crate::powerup::{PowerUp, PowerUps};
+use crate::scene::Scene;
+use crate::spawner::{Kind, Spawner};
+use crate::tilemap::Tilemap;
+use crate::timestep::DT;
+
+// The sprite sheet is 198x174px: the acorn on the left, the squirrel frames
+// stacked next to it, and a column of 32px items (the pinecone up top) on the
+// right (the pinecone, then the speed, magnet, double points and shield
+// power-ups).  Sheet regions are in UVs, so we divide by these.
+pub const SHEET_W: f32 = 198.0;
+pub const SHEET_H: f32 = 174.0;
+
+// in pixels per second; how fast acorns fall comes from the difficulty tiers
+const SQUIRREL_SPEED: f32 = 120.0;
+// the forest floor, for anywhere the level doesn't have solid tiles under the squirrel
+pub const GROUND_Y: f32 = 32.0;
+
+// how long the squirrel is dazed for after a pinecone to the head
+pub const STUN_SECONDS: f32 = 1.0;
+// while dazed the squirrel flashes red, switching every this many seconds
+const FLASH_SECONDS: f32 = 0.1;
+const FLASH_TINT: [f32; 4] = [1.0, 0.35, 0.35, 1.0];
+
+// acorns turn this many radians per second as they fall
+const ACORN_SPIN: f32 = 2.0;
+
+// how often something that falls is a power-up
+const POWER_UP_CHANCE: f32 = 0.04;
+// walking speed is multiplied by this with the speed power-up
+pub const SPEED_BOOST: f32 = 1.75;
+// the magnet reaches acorns whose middle is within this many pixels (sideways)
+// of the squirrel's, and drags them over this many pixels per second
+pub const MAGNET_RANGE: f32 = 250.0;
+pub const MAGNET_PULL: f32 = 200.0;
+
+// every this many catches in a row adds one to the combo multiplier...
+pub const COMBO_STEP: u32 = 5;
+// ...up to this
+pub const MAX_COMBO_MULTIPLIER: usize = 5;
+// an acorn caught within this many pixels (sideways) of the squirrel's head
+// is a perfect catch, and worth this many extra points before multipliers
+pub const PERFECT_RANGE: f32 = 12.0;
+pub const PERFECT_BONUS: usize = 2;
+
+// the wave name pops up in the middle, this far up the screen
+const WAVE_POPUP_HEIGHT: f32 = 0.6;
+
+// a bit bigger than most sprites, so each one only lands in a few cells
+const BROADPHASE_CELL: f32 = 128.0;
+
+// Things that happened during a step that the rest of the game may want to react to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameEvent {
+    Caught { points: usize, perfect: bool },
+    Missed,
+    // a hazard landed on the squirrel
+    Hit,
+    // ...but the shield took it
+    Shielded,
+    Collected(PowerUp),
+    // a name went into the high score table, so it wants saving
+    HighScore,
+    // the player paused mid-run, a good time to save it
+    Paused,
+    // the run is finished for good, so there's nothing left to resume
+    RunOver,
+    // the goal of this wave (counting from 0) was met
+    WaveCleared(usize),
+}
+
+// The World is everything the game needs to simulate one step: the characters,
+// the score, and the last input it saw.  It knows nothing about windows or GPUs,
+// so it can be stepped (and tested) on its own.
+pub struct World {
+    pub squirrel: Char_action,
+    pub falling: Spawner,
+    pub gs: GameState,
+    pub difficulty: Difficulty,
+    // how far everything can go, and how much the camera shows
+    pub size: WorldSize,
+    // the platforms; like the difficulty, not part of a run
+    pub map: Tilemap,
+    // the waves to get through, or None to keep going by the difficulty tiers
+    pub level: Option<Level>,
+    pub input: InputFrame,
+    // filled in by step, emptied by whoever is listening
+    pub events: Vec<GameEvent>,
+    // floating "+3"s over recent catches
+    pub popups: Vec<Popup>,
+    // acorns and power-ups that were just caught, fading out
+    pub fading: Vec<Fade>,
+    // starts empty; whoever owns the World loads and saves it
+    pub high_scores: HighScores,
+    // where the last run landed in high_scores, if it made it
+    pub last_rank: Option<usize>,
+    // seconds simulated since the World was made, except while paused.  The
+    // scenery moves by this rather than the wall clock, so it stops when the
+    // game does and replays look the same.
+    pub clock: f32,
+    // rebuilt every step; kept around so its memory is too
+    broadphase: SpatialHash,
+}
+
+impl World {
+    pub fn new(seed: u64) -> World {
+        let difficulty = Difficulty::default();
+        let size = WorldSize::default();
+        let (squirrel, falling) = new_characters(&difficulty, size);
+        World {
+            squirrel,
+            falling,
+            gs: game_state::init_game_state(seed),
+            difficulty,
+            size,
+            map: Tilemap::default(),
+            level: None,
+            input: InputFrame::default(),
+            events: Vec::new(),
+            popups: Vec::new(),
+            fading: Vec::new(),
+            high_scores: HighScores::default(),
+            last_rank: None,
+            clock: 0.0,
+            broadphase: SpatialHash::new(BROADPHASE_CELL),
+        }
+    }
+
+    // Put everyone back where they started and begin playing.  The RNG carries
+    // on from where it was, so a whole session stays reproducible from one seed.
+    pub fn start_run(&mut self) {
+        let (squirrel, falling) = new_characters(&self.difficulty, self.size);
+        self.squirrel = squirrel;
+        self.falling = falling;
+        self.gs.score = 0;
+        self.gs.combo = 0;
+        self.popups.clear();
+        self.fading.clear();
+        self.last_rank = None;
+        self.gs.elapsed = 0.0;
+        self.gs.tier = 0;
+        self.gs.lives = START_LIVES;
+        self.gs.stunned = 0.0;
+        self.gs.power_ups = PowerUps::default();
+        self.gs.cleared = false;
+        self.gs.scene = Scene::Playing;
+        self.start_wave(0);
+    }
+
+    // Advance the game by one fixed step of DT seconds using this frame's input.
+    // What that means depends on which scene we're in.
+    pub fn step(&mut self, input: &InputFrame) {
+        self.input = *input;
+        let scene = self.gs.scene;
+        if scene != Scene::Paused {
+            self.clock += DT;
+        }
+        scene.update(self, input);
+    }
+
+    // One step of actual gameplay
+    pub fn play_step(&mut self, input: &InputFrame) {
+        self.gs.elapsed += DT;
+        self.gs.power_ups.tick(DT);
+        popup::update(&mut self.popups, DT);
+        fade::update(&mut self.fading, DT);
+        self.squirrel.store_prev();
+
+        self.squirrel.speed = if self.gs.power_ups.active(PowerUp::Speed) {
+            SQUIRREL_SPEED * SPEED_BOOST
+        } else {
+            SQUIRREL_SPEED
+        };
+
+        if self.gs.stunned > 0.0 {
+            // seeing stars, can't move
+            self.gs.stunned = (self.gs.stunned - DT).max(0.0);
+            self.squirrel.animation.stop();
+        } else if input.left {
+            self.squirrel.face_left();
+            self.squirrel.walk(DT);
+            self.squirrel.animation.tick();
+        } else if input.right {
+            self.squirrel.face_right();
+            self.squirrel.walk(DT);
+            self.squirrel.animation.tick();
+        } else {
+            self.squirrel.animation.stop();
+        }
+        let flash = self.gs.stunned > 0.0 && ((self.gs.stunned / FLASH_SECONDS) as u32).is_multiple_of(2);
+        self.squirrel.tint = if flash { FLASH_TINT } else { [1.0; 4] };
+        // no jumping while stunned, but what goes up still comes down
+        self.squirrel.physics.jump_input(input.jump && self.gs.stunned <= 0.0);
+        let ground = self
+            .map
+            .ground_under(&self.squirrel.hitbox_rect(), self.squirrel.prev_screen_region[1])
+            .unwrap_or(GROUND_Y);
+        self.squirrel.apply_physics(ground, DT);
+        self.squirrel.keep_in(self.size);
+
+        self.apply_difficulty();
+        self.falling.update(DT, &mut self.gs.rng);
+
+        // Move everything that's falling, and take out what landed
+        let squirrel = &self.squirrel;
+        let size = self.size;
+        let magnet = self.gs.power_ups.active(PowerUp::Magnet);
+        let mut missed = 0;
+        self.falling.objects.retain_mut(|object| {
+            object.body.store_prev();
+            if magnet && object.kind == Kind::Acorn {
+                pull_towards(squirrel, &mut object.body);
+            }
+            let landed = object.body.move_down(DT);
+            if !landed {
+                object.body.keep_in(size);
+            }
+            // dodging a hazard all the way to the ground is free
+            if landed && object.kind == Kind::Acorn {
+                missed += 1;
+            }
+            !landed
+        });
+
+        // Then see what the squirrel got
+        let touching = self.squirrel_touching();
+
+        let mut caught = vec![];
+        let mut hits = 0;
+        let mut collected = vec![];
+        for &i in &touching {
+            let object = &self.falling.objects[i];
+            match object.kind {
+                Kind::Acorn => caught.push(object.body.screen_region),
+                Kind::Hazard => hits += 1,
+                Kind::PowerUp(power_up) => collected.push(power_up),
+            }
+            if object.kind != Kind::Hazard {
+                self.fading.push(Fade::new(object.body.clone()));
+            }
+        }
+        // touching is sorted, so this can just look each index up
+        let mut i = 0;
+        self.falling.objects.retain(|_| {
+            i += 1;
+            touching.binary_search(&(i - 1)).is_err()
+        });
+
+        for acorn in caught {
+            self.score_catch(acorn);
+        }
+        for power_up in collected {
+            self.gs.power_ups.collect(power_up);
+            self.events.push(GameEvent::Collected(power_up));
+        }
+        for _ in 0..missed {
+            self.miss();
+        }
+        for _ in 0..hits {
+            self.hit();
+        }
+        self.update_wave();
+    }
+
+    // Point the spawner at the current wave if there's a level, otherwise at
+    // whatever tier the score (or time) has reached
+    fn apply_difficulty(&mut self) {
+        if let Some(wave) = self.level.as_ref().and_then(|level| level.waves.get(self.gs.wave)) {
+            self.falling.fall_speed = wave.fall_speed;
+            self.falling.interval = wave.spawn_interval;
+            self.falling.max_live = wave.max_live;
+            self.falling.hazard_chance = wave.hazard_chance;
+            self.falling.power_up_chance = wave.power_up_chance;
+            self.falling.x_range = wave.x;
+            return;
+        }
+        let tier_index = self.difficulty.tier_index_at(self.gs.score, self.gs.elapsed);
+        let tier = &self.difficulty.tiers[tier_index];
+        if tier_index != self.gs.tier {
+            self.gs.tier = tier_index;
+            log::info!(
+                "Difficulty tier {}: falling at {}px/s every {}s, up to {} at once, {}% hazards",
+                tier_index, tier.fall_speed, tier.spawn_interval, tier.max_acorns, tier.hazard_chance * 100.0
+            );
+        }
+        self.falling.fall_speed = tier.fall_speed;
+        self.falling.interval = tier.spawn_interval;
+        self.falling.max_live = tier.max_acorns;
+        self.falling.hazard_chance = tier.hazard_chance;
+    }
+
+    // Move on once the wave's goal is met, or end the run if its time ran out first
+    fn update_wave(&mut self) {
+        let Some(wave) = self.level.as_ref().and_then(|level| level.waves.get(self.gs.wave)) else {
+            return;
+        };
+        if self.gs.scene != Scene::Playing {
+            // out of lives already
+            return;
+        }
+        self.gs.wave_time += DT;
+        let out_of_time = wave.seconds.is_some_and(|seconds| self.gs.wave_time >= seconds);
+        let done = match wave.goal {
+            Goal::Catch(acorns) => self.gs.wave_caught >= acorns,
+            Goal::Score(points) => self.gs.score - self.gs.wave_start_score >= points,
+            Goal::Survive => out_of_time,
+        };
+        if done {
+            self.events.push(GameEvent::WaveCleared(self.gs.wave));
+            self.start_wave(self.gs.wave + 1);
+        } else if out_of_time {
+            self.end_run();
+        }
+    }
+
+    // Get going on a wave, or if that was the last one, finish the level
+    fn start_wave(&mut self, wave: usize) {
+        let Some(level) = &self.level else {
+            return;
+        };
+        let Some(name) = level.waves.get(wave).map(|w| w.name.clone()) else {
+            self.gs.cleared = true;
+            self.end_run();
+            return;
+        };
+        self.gs.wave = wave;
+        self.gs.wave_time = 0.0;
+        self.gs.wave_caught = 0;
+        self.gs.wave_start_score = self.gs.score;
+        let (x, y) = (self.size.width / 2.0, self.size.height * WAVE_POPUP_HEIGHT);
+        self.popups.push(Popup::new(format!("Wave {}: {}", wave + 1, name), x, y));
+    }
+
+    // Which falling objects the squirrel's hitbox overlaps right now, by index.
+    // The broadphase narrows it down to what's nearby, and only those get a
+    // proper hitbox check.
+    pub fn squirrel_touching(&mut self) -> Vec<usize> {
+        self.broadphase.clear();
+        for (i, object) in self.falling.objects.iter().enumerate() {
+            self.broadphase.insert(i, &object.body.hitbox_rect());
+        }
+        let squirrel_box = self.squirrel.hitbox_rect();
+        self.broadphase
+            .query(&squirrel_box)
+            .into_iter()
+            .filter(|&i| squirrel_box.overlaps(&self.falling.objects[i].body.hitbox_rect()))
+            .collect()
+    }
+
+    // Work out what catching the acorn at this spot is worth and put it up on screen
+    fn score_catch(&mut self, acorn: [f32; 4]) {
+        self.gs.combo += 1;
+        self.gs.wave_caught += 1;
+        let combo = combo_multiplier(self.gs.combo);
+        let perfect = is_perfect(&self.squirrel, acorn);
+        let base = if perfect { 1 + PERFECT_BONUS } else { 1 };
+        let points = base * combo * self.gs.power_ups.multiplier();
+        self.gs.score += points;
+        self.events.push(GameEvent::Caught { points, perfect });
+
+        let mut text = if perfect { format!("Perfect! +{}", points) } else { format!("+{}", points) };
+        if combo > 1 {
+            text.push_str(&format!(" combo x{}", combo));
+        }
+        let [x, y, w, _] = acorn;
+        self.popups.push(Popup::new(text, x + w / 2.0, y));
+    }
+
+    // An acorn hit the ground
+    fn miss(&mut self) {
+        self.events.push(GameEvent::Missed);
+        self.gs.combo = 0;
+        self.lose_life();
+    }
+
+    // A hazard hit the squirrel: that hurts and leaves it stunned for a bit,
+    // unless there's a shield up
+    fn hit(&mut self) {
+        if self.gs.power_ups.use_shield() {
+            self.events.push(GameEvent::Shielded);
+            return;
+        }
+        self.events.push(GameEvent::Hit);
+        self.gs.combo = 0;
+        self.gs.stunned = STUN_SECONDS;
+        self.lose_life();
+    }
+
+    // Lose a life, and the game if that was the last one
+    fn lose_life(&mut self) {
+        // the run already ended earlier in this step
+        if self.gs.scene != Scene::Playing {
+            return;
+        }
+        self.gs.lives = self.gs.lives.saturating_sub(1);
+        if self.gs.lives == 0 {
+            self.end_run();
+        }
+    }
+
+    // The run is over: ask for a name if the score made the table.  Only once,
+    // though; anything else going wrong in the same step doesn't end it again.
+    pub fn end_run(&mut self) {
+        if !matches!(self.gs.scene, Scene::Playing | Scene::Paused) {
+            return;
+        }
+        self.events.push(GameEvent::RunOver);
+        if self.high_scores.qualifies(self.gs.score) {
+            self.gs.chars_typed.clear();
+            self.gs.scene = Scene::EnterName;
+        } else {
+            self.gs.scene = Scene::GameOver;
+        }
+    }
+
+    // Keyboard text from the window.  Only does anything while a name is being typed.
+    pub fn type_char(&mut self, c: char) {
+        if self.gs.scene != Scene::EnterName {
+            return;
+        }
+        if c == '\u{8}' {
+            // backspace
+            self.gs.chars_typed.pop();
+        } else if !c.is_control() && self.gs.chars_typed.chars().count() < MAX_NAME_LEN {
+            self.gs.chars_typed.push(c);
+        }
+    }
+
+    // Put the typed name into the table with this run's score
+    pub fn submit_name(&mut self) {
+        let name = match self.gs.chars_typed.trim() {
+            "" => "Squirrel".to_string(),
+            name => name.to_string(),
+        };
+        self.last_rank = self.high_scores.insert(Entry {
+            name,
+            score: self.gs.score,
+            date: highscores::today(),
+            seed: self.gs.seed,
+            seconds: self.gs.elapsed,
+        });
+        self.events.push(GameEvent::HighScore);
+        self.gs.scene = Scene::GameOver;
+    }
+
+    // A fingerprint of what the game is being played on: the difficulty tiers,
+    // the level and the map.  FNV-1a, so it comes out the same on every build.
+    pub fn content_hash(&self) -> u64 {
+        let content = format!("{:?}{:?}{:?}", self.difficulty, self.level, self.map);
+        content
+            .bytes()
+            .fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+    }
+
+    pub fn drain_events(&mut self) -> std::vec::Drain<'_, GameEvent> {
+        self.events.drain(..)
+    }
+}
+
+// 1 to start with, and one more for every COMBO_STEP catches in a row
+pub fn combo_multiplier(combo: u32) -> usize {
+    (1 + (combo / COMBO_STEP) as usize).min(MAX_COMBO_MULTIPLIER)
+}
+
+// Did this acorn come down right on the squirrel's head?  The sheet's squirrel
+// faces left, so its head is at the left end unless it's been flipped.
+fn is_perfect(squirrel: &Char_action, acorn: [f32; 4]) -> bool {
+    let drawn = Rect::from_region(squirrel.screen_region);
+    let head = if squirrel.flip_x {
+        drawn.right() - drawn.w / 4.0
+    } else {
+        drawn.left() + drawn.w / 4.0
+    };
+    // a strip straight up from the head; the acorn's middle has to be in it
+    let sweet_spot = Rect::new(head - PERFECT_RANGE, drawn.y, PERFECT_RANGE * 2.0, f32::MAX);
+    let (x, y) = Rect::from_region(acorn).center();
+    sweet_spot.contains_point(x, y)
+}
+
+// The magnet drags an acorn sideways towards the middle of the squirrel
+fn pull_towards(squirrel: &Char_action, acorn: &mut Char_action) {
+    let (squirrel_mid, _) = Rect::from_region(squirrel.screen_region).center();
+    let (acorn_mid, _) = Rect::from_region(acorn.screen_region).center();
+    let gap = squirrel_mid - acorn_mid;
+    if gap.abs() < MAGNET_RANGE {
+        acorn.screen_region[0] += gap.signum() * gap.abs().min(MAGNET_PULL * DT);
+    }
+}
+
+fn new_characters(difficulty: &Difficulty, size: WorldSize) -> (Char_action, Spawner) {
+    // frames will be a series of frames
+    let squirrel_sheet_positions: Vec<[f32; 4]> = vec![
+        // frame 1 sheet position
+        [126.0 / SHEET_W, 25.0 / SHEET_H, 32.0 / SHEET_W, 21.0 / SHEET_H],
+        // frame 2 sheet position
+        [126.0 / SHEET_W, 48.0 / SHEET_H, 32.0 / SHEET_W, 22.0 / SHEET_H],
+        // frame 3 sheet position
+        [126.0 / SHEET_W, 72.0 / SHEET_H, 28.0 / SHEET_W, 23.0 / SHEET_H],
+        // frame 4 sheet position
+        [126.0 / SHEET_W, 97.0 / SHEET_H, 35.0 / SHEET_W, 23.0 / SHEET_H],
+        // frame 5 sheet position
+        [126.0 / SHEET_W, 122.0 / SHEET_H, 33.0 / SHEET_W, 22.0 / SHEET_H],
+    ];
+
+    let squirrel_animation: Animation = Animation {
+        states: squirrel_sheet_positions,
+        frame_counter: 0,
+        rate: 7,
+        state_number: 0,
+    };
+
+    let acorn_animation: Animation = Animation {
+        states: vec![[0.0, 0.0, 123.0 / SHEET_W, 172.0 / SHEET_H]],
+        frame_counter: 0,
+        rate: 7,
+        state_number: 0,
+    };
+
+    let pinecone_animation: Animation = Animation {
+        states: vec![[164.0 / SHEET_W, 1.0 / SHEET_H, 32.0 / SHEET_W, 32.0 / SHEET_H]],
+        frame_counter: 0,
+        rate: 7,
+        state_number: 0,
+    };
+
+    let mut squirrel = Char_action::new([32.0, GROUND_Y, 100.0, 100.0], squirrel_animation, SQUIRREL_SPEED, true, 0);
+    // the frames are stretched to fill the sprite, but the fluffy top of the tail shouldn't catch anything
+    squirrel.hitbox = Hitbox { x: 0.05, y: 0.0, w: 0.9, h: 0.75 };
+    // everything the spawner makes is a copy of one of these; they come after the squirrel in the sprite list.
+    // They face left so they're drawn the way they are on the sheet.
+    let first = difficulty.tier_at(0, 0.0);
+    let mut acorn = Char_action::new([0.0, size.height, 55.0, 55.0], acorn_animation, first.fall_speed, false, 1);
+    acorn.hitbox = Hitbox { x: 0.1, y: 0.0, w: 0.8, h: 0.9 };
+    acorn.spin = ACORN_SPIN;
+    // a little forgiving, so a near miss doesn't hurt
+    let mut pinecone = Char_action::new([0.0, size.height, 50.0, 50.0], pinecone_animation, first.fall_speed, false, 1);
+    pinecone.hitbox = Hitbox { x: 0.2, y: 0.1, w: 0.6, h: 0.8 };
+    // the power-up icons are stacked under the pinecone, in PowerUp::ALL order
+    let power_ups: Vec<Char_action> = (0..PowerUp::ALL.len())
+        .map(|i| {
+            let y = 37.0 + 34.0 * i as f32;
+            let animation = Animation {
+                states: vec![[164.0 / SHEET_W, y / SHEET_H, 32.0 / SHEET_W, 32.0 / SHEET_H]],
+                frame_counter: 0,
+                rate: 7,
+                state_number: 0,
+            };
+            Char_action::new([0.0, size.height, 45.0, 45.0], animation, first.fall_speed, false, 1)
+        })
+        .collect();
+
+    let mut spawner = Spawner::new(acorn, pinecone, power_ups, first.spawn_interval, first.max_acorns, size);
+    spawner.power_up_chance = POWER_UP_CHANCE;
+    (squirrel, spawner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bounds::Edges;
+
+    // A world that has already left the title screen
+    fn playing(seed: u64) -> World {
+        let mut world = World::new(seed);
+        world.step(&InputFrame { start: true, ..Default::default() });
+        world
+    }
+
+    // Move a falling object to just above the middle of the squirrel, so it lands on it next step
+    fn drop_on_squirrel(world: &mut World, i: usize) {
+        let (mid, _) = Rect::from_region(world.squirrel.screen_region).center();
+        let body = &mut world.falling.objects[i].body;
+        body.screen_region[0] = mid - body.screen_region[2] / 2.0;
+        body.screen_region[1] = world.squirrel.screen_region[1] + 20.0;
+    }
+
+    // Keep everything falling over on the left and the squirrel on the right, so nothing gets caught
+    fn park_squirrel(world: &mut World) {
+        world.falling.x_range = (0.0, 300.0);
+        world.squirrel.screen_region[0] = 800.0;
+    }
+
+    #[test]
+    fn walking_moves_the_squirrel() {
+        let mut world = playing(0);
+        let start_x = world.squirrel.screen_region[0];
+        let right = InputFrame { right: true, ..Default::default() };
+        for _ in 0..10 {
+            world.step(&right);
+        }
+        assert!(world.squirrel.screen_region[0] > start_x);
+        assert!(world.squirrel.facing_right);
+    }
+
+    #[test]
+    fn turning_around_stays_in_place() {
+        let mut world = playing(0);
+        let region = world.squirrel.screen_region;
+        world.squirrel.face_right();
+        assert_eq!(world.squirrel.screen_region, region);
+        assert!(world.squirrel.flip_x);
+        world.squirrel.face_left();
+        assert_eq!(world.squirrel.screen_region, region);
+        assert!(!world.squirrel.flip_x);
+    }
+
+    #[test]
+    fn the_squirrel_stops_at_the_edge_or_wraps_around() {
+        let mut world = playing(0);
+        let left = InputFrame { left: true, ..Default::default() };
+        for _ in 0..120 {
+            world.step(&left);
+        }
+        assert_eq!(Rect::from_region(world.squirrel.screen_region).left(), 0.0);
+
+        world.squirrel.edges = Edges::Wrap;
+        for _ in 0..60 {
+            world.step(&left);
+        }
+        let drawn = Rect::from_region(world.squirrel.screen_region);
+        assert!(drawn.right() > world.size.width - 100.0, "{:?}", drawn);
+        // and it was drawn coming in from the side rather than sliding across
+        let prev = Rect::from_region(world.squirrel.prev_screen_region);
+        assert!((drawn.x - prev.x).abs() < 10.0);
+    }
+
+    #[test]
+    fn everything_spawns_all_the_way_on_screen() {
+        let mut world = playing(9);
+        world.falling.max_live = 1000;
+        for _ in 0..500 {
+            world.falling.spawn(Kind::Acorn, &mut world.gs.rng);
+        }
+        let screen = Rect::new(0.0, 0.0, world.size.width, world.size.height);
+        assert!(world.falling.objects.iter().all(|f| screen.contains(&Rect::from_region(f.body.screen_region))));
+        // right up against the right side sometimes
+        assert!(world.falling.objects.iter().any(|f| f.body.screen_region[0] > 950.0));
+    }
+
+    #[test]
+    fn jumping_reaches_acorns_up_high() {
+        let mut world = playing(0);
+        world.falling.objects.clear();
+        world.falling.spawn(Kind::Acorn, &mut world.gs.rng);
+        // hanging in the air well over the squirrel's head
+        drop_on_squirrel(&mut world, 0);
+        world.falling.objects[0].body.screen_region[1] = GROUND_Y + 200.0;
+        world.falling.objects[0].body.speed = 0.0;
+        for _ in 0..60 {
+            world.step(&InputFrame::default());
+        }
+        assert_eq!(world.gs.score, 0);
+
+        let jump = InputFrame { jump: true, ..Default::default() };
+        for _ in 0..30 {
+            world.step(&jump);
+        }
+        assert_eq!(world.gs.score, 1);
+        // and back down again, even with jump still held
+        for _ in 0..60 {
+            world.step(&jump);
+        }
+        assert!(world.squirrel.physics.on_ground);
+    }
+
+    #[test]
+    fn branches_hold_the_squirrel_up() {
+        let mut world = playing(0);
+        world.map = Tilemap::from_csv("0,0,0,0,0,0\n0,0,0,3,3,0\n0,0,0,0,0,0\n1,1,1,1,1,1\n").unwrap();
+        // under the branch, whose top is at y = 96
+        world.squirrel.screen_region[0] = 100.0;
+        let jump = InputFrame { jump: true, ..Default::default() };
+        for _ in 0..90 {
+            world.step(&jump);
+        }
+        assert_eq!(world.squirrel.screen_region[1], 96.0);
+        assert!(world.squirrel.physics.on_ground);
+
+        // walking off the end drops back down to the ground
+        let left = InputFrame { left: true, ..Default::default() };
+        for _ in 0..90 {
+            world.step(&left);
+        }
+        assert_eq!(world.squirrel.screen_region[1], GROUND_Y);
+    }
+
+    #[test]
+    fn no_jumping_while_stunned() {
+        let mut world = playing(0);
+        catch(&mut world, Kind::Hazard);
+        world.step(&InputFrame { jump: true, ..Default::default() });
+        assert_eq!(world.squirrel.screen_region[1], GROUND_Y);
+    }
+
+    #[test]
+    fn catching_an_acorn_scores_and_removes_it() {
+        let mut world = playing(0);
+        // Drop an acorn right on top of the squirrel's head
+        world.falling.spawn(Kind::Acorn, &mut world.gs.rng);
+        drop_on_squirrel(&mut world, 0);
+        world.step(&InputFrame::default());
+        assert_eq!(world.gs.score, 1);
+        // only the freshly spawned acorn up top is left
+        assert_eq!(world.falling.objects.len(), 1);
+        assert_eq!(world.drain_events().collect::<Vec<_>>(), vec![GameEvent::Caught { points: 1, perfect: false }]);
+    }
+
+    #[test]
+    fn acorns_pile_up_to_the_limit() {
+        let mut world = playing(0);
+        park_squirrel(&mut world);
+        let mut most = 0;
+        for _ in 0..(10.0 / DT) as usize {
+            world.step(&InputFrame::default());
+            most = most.max(world.falling.objects.len());
+        }
+        assert!(most > 1);
+        assert!(most <= world.difficulty.tiers[0].max_acorns);
+    }
+
+    #[test]
+    fn same_seed_same_run() {
+        let inputs: Vec<InputFrame> = (0..2000)
+            .map(|i| InputFrame { left: (i / 90) % 2 == 0, right: (i / 90) % 2 == 1, ..Default::default() })
+            .collect();
+        let run = |seed| {
+            let mut world = playing(seed);
+            let mut spawns = vec![];
+            for input in inputs.iter() {
+                world.step(input);
+                spawns.extend(world.falling.objects.iter().map(|a| a.body.screen_region[0]));
+            }
+            (spawns, world.gs.score)
+        };
+        assert_eq!(run(42), run(42));
+        assert_ne!(run(42).0, run(43).0);
+    }
+
+    #[test]
+    fn scenes_follow_the_flow() {
+        let start = InputFrame { start: true, ..Default::default() };
+        let pause = InputFrame { pause: true, ..Default::default() };
+        let nothing = InputFrame::default();
+
+        let mut world = World::new(0);
+        world.step(&nothing);
+        assert_eq!(world.gs.scene, Scene::Title);
+        world.step(&start);
+        assert_eq!(world.gs.scene, Scene::Playing);
+        world.step(&nothing);
+        world.step(&pause);
+        assert_eq!(world.gs.scene, Scene::Paused);
+
+        // nothing moves while paused, not even the scenery
+        let acorn = world.falling.objects[0].body.screen_region;
+        let clock = world.clock;
+        world.step(&nothing);
+        assert_eq!(world.falling.objects[0].body.screen_region, acorn);
+        assert_eq!(world.clock, clock);
+
+        world.step(&pause);
+        assert_eq!(world.gs.scene, Scene::Playing);
+        world.step(&pause);
+        world.step(&start);
+        assert_eq!(world.gs.scene, Scene::GameOver);
+        world.step(&start);
+        assert_eq!(world.gs.scene, Scene::Title);
+    }
+
+    #[test]
+    fn missing_acorns_costs_lives_then_the_game() {
+        let mut world = playing(0);
+        park_squirrel(&mut world);
+        world.step(&InputFrame::default());
+        world.falling.objects[0].body.screen_region[1] = 1.0;
+        world.step(&InputFrame::default());
+        assert_eq!(world.gs.lives, START_LIVES - 1);
+        assert!(world.drain_events().any(|e| e == GameEvent::Missed));
+        assert!(world.falling.objects.is_empty());
+
+        while world.gs.scene == Scene::Playing {
+            world.step(&InputFrame::default());
+        }
+        assert_eq!(world.gs.lives, 0);
+        assert_eq!(world.gs.scene, Scene::GameOver);
+    }
+
+    #[test]
+    fn two_misses_at_once_only_end_the_run_once() {
+        let mut world = playing(0);
+        park_squirrel(&mut world);
+        // good enough for the table, so it should ask for a name and stay asking
+        world.gs.score = 5;
+        world.gs.lives = 1;
+        world.falling.objects.clear();
+        world.falling.spawn(Kind::Acorn, &mut world.gs.rng);
+        world.falling.spawn(Kind::Acorn, &mut world.gs.rng);
+        for object in world.falling.objects.iter_mut() {
+            object.body.screen_region[1] = 1.0;
+        }
+        world.step(&InputFrame::default());
+        world.type_char('N');
+        let events: Vec<_> = world.drain_events().collect();
+        assert_eq!(events.iter().filter(|&&e| e == GameEvent::RunOver).count(), 1, "{:?}", events);
+        assert_eq!(world.gs.scene, Scene::EnterName);
+        assert_eq!(world.gs.chars_typed, "N");
+        // ending it again from outside does nothing either
+        world.end_run();
+        assert!(world.drain_events().next().is_none());
+        assert_eq!(world.gs.chars_typed, "N");
+    }
+
+    #[test]
+    fn pinecones_hurt_and_stun() {
+        let mut world = playing(0);
+        world.falling.spawn(Kind::Hazard, &mut world.gs.rng);
+        drop_on_squirrel(&mut world, 0);
+        world.step(&InputFrame::default());
+        assert_eq!(world.gs.score, 0);
+        assert_eq!(world.gs.lives, START_LIVES - 1);
+        assert_eq!(world.drain_events().collect::<Vec<_>>(), vec![GameEvent::Hit]);
+
+        // can't walk off while stunned...
+        let left = InputFrame { left: true, ..Default::default() };
+        let x = world.squirrel.screen_region[0];
+        for _ in 0..(STUN_SECONDS / DT) as usize - 1 {
+            world.step(&left);
+        }
+        assert_eq!(world.squirrel.screen_region[0], x);
+        // ...but can once it wears off
+        for _ in 0..5 {
+            world.step(&left);
+        }
+        assert!(world.squirrel.screen_region[0] < x);
+    }
+
+    #[test]
+    fn dodged_pinecones_are_free() {
+        let mut world = playing(0);
+        park_squirrel(&mut world);
+        world.falling.objects.clear();
+        world.falling.spawn(Kind::Hazard, &mut world.gs.rng);
+        world.falling.objects[0].body.screen_region[1] = 1.0;
+        world.step(&InputFrame::default());
+        assert_eq!(world.gs.lives, START_LIVES);
+        assert!(world.drain_events().next().is_none());
+        assert!(world.falling.objects.iter().all(|f| f.kind == Kind::Acorn));
+    }
+
+    // Drop something right on the squirrel and take one step
+    fn catch(world: &mut World, kind: Kind) {
+        world.falling.objects.clear();
+        world.falling.spawn(kind, &mut world.gs.rng);
+        drop_on_squirrel(world, 0);
+        world.step(&InputFrame::default());
+    }
+
+    #[test]
+    fn catches_fade_out_and_hits_flash() {
+        let mut world = playing(0);
+        catch(&mut world, Kind::Acorn);
+        assert_eq!(world.fading.len(), 1);
+        let opacity = world.fading[0].body.tint[3];
+        world.step(&InputFrame::default());
+        assert!(world.fading[0].body.tint[3] < opacity);
+        assert!(world.fading[0].body.scale > 1.0);
+        for _ in 0..(fade::FADE_SECONDS / DT) as usize {
+            world.step(&InputFrame::default());
+        }
+        assert!(world.fading.is_empty());
+
+        // pinecones don't stick around, but the squirrel goes red on and off
+        catch(&mut world, Kind::Hazard);
+        assert!(world.fading.is_empty());
+        let mut tints = vec![];
+        for _ in 0..(STUN_SECONDS / DT) as usize + 2 {
+            tints.push(world.squirrel.tint);
+            world.step(&InputFrame::default());
+        }
+        assert!(tints.contains(&FLASH_TINT) && tints.contains(&[1.0; 4]));
+        assert_eq!(world.squirrel.tint, [1.0; 4]);
+    }
+
+    #[test]
+    fn falling_things_are_not_mirrored() {
+        let mut world = playing(0);
+        world.falling.objects.clear();
+        world.falling.spawn(Kind::Acorn, &mut world.gs.rng);
+        world.falling.spawn(Kind::Hazard, &mut world.gs.rng);
+        for power_up in PowerUp::ALL {
+            world.falling.spawn(Kind::PowerUp(power_up), &mut world.gs.rng);
+        }
+        assert!(world.falling.objects.iter().all(|f| !f.body.flip_x));
+    }
+
+    #[test]
+    fn acorns_spin_as_they_fall() {
+        let mut world = playing(0);
+        world.falling.objects.clear();
+        world.falling.spawn(Kind::Acorn, &mut world.gs.rng);
+        world.falling.spawn(Kind::Hazard, &mut world.gs.rng);
+        world.step(&InputFrame::default());
+        assert!((world.falling.objects[0].body.rotation - ACORN_SPIN * DT).abs() < 0.0001);
+        assert_eq!(world.falling.objects[1].body.rotation, 0.0);
+    }
+
+    #[test]
+    fn speed_boost_walks_faster_until_it_runs_out() {
+        let mut world = playing(0);
+        catch(&mut world, Kind::PowerUp(PowerUp::Speed));
+        assert!(world.drain_events().any(|e| e == GameEvent::Collected(PowerUp::Speed)));
+        let left = InputFrame { left: true, ..Default::default() };
+        let x = world.squirrel.screen_region[0];
+        world.step(&left);
+        let boosted = x - world.squirrel.screen_region[0];
+        assert!((boosted - SQUIRREL_SPEED * SPEED_BOOST * DT).abs() < 0.001);
+
+        for _ in 0..(PowerUp::Speed.duration() / DT) as usize {
+            world.step(&InputFrame::default());
+        }
+        let x = world.squirrel.screen_region[0];
+        world.step(&left);
+        assert!((x - world.squirrel.screen_region[0] - SQUIRREL_SPEED * DT).abs() < 0.001);
+    }
+
+    #[test]
+    fn magnet_pulls_acorns_in() {
+        let mut world = playing(0);
+        catch(&mut world, Kind::PowerUp(PowerUp::Magnet));
+        world.falling.objects.clear();
+        world.falling.spawn(Kind::Acorn, &mut world.gs.rng);
+        world.falling.spawn(Kind::Hazard, &mut world.gs.rng);
+        let squirrel_mid = world.squirrel.screen_region[0] + world.squirrel.screen_region[2] / 2.0;
+        // one acorn and one pinecone just in range, off to the right
+        for object in world.falling.objects.iter_mut() {
+            object.body.screen_region[0] = squirrel_mid + MAGNET_RANGE - 60.0;
+        }
+        let before = world.falling.objects[0].body.screen_region[0];
+        world.step(&InputFrame::default());
+        let acorn = &world.falling.objects[0];
+        assert_eq!(acorn.kind, Kind::Acorn);
+        assert!((before - acorn.body.screen_region[0] - MAGNET_PULL * DT).abs() < 0.001);
+        // hazards aren't magnetic
+        assert_eq!(world.falling.objects[1].body.screen_region[0], before);
+    }
+
+    #[test]
+    fn double_points_doubles_the_score() {
+        let mut world = playing(0);
+        catch(&mut world, Kind::PowerUp(PowerUp::DoublePoints));
+        catch(&mut world, Kind::Acorn);
+        assert_eq!(world.gs.score, 2);
+        // a second pickup while it's running banks more time instead
+        catch(&mut world, Kind::PowerUp(PowerUp::DoublePoints));
+        assert!(world.gs.power_ups.remaining(PowerUp::DoublePoints) > PowerUp::DoublePoints.duration());
+        catch(&mut world, Kind::Acorn);
+        assert_eq!(world.gs.score, 4);
+    }
+
+    #[test]
+    fn shield_takes_one_hit() {
+        let mut world = playing(0);
+        catch(&mut world, Kind::PowerUp(PowerUp::Shield));
+        world.drain_events().for_each(drop);
+        catch(&mut world, Kind::Hazard);
+        assert_eq!(world.gs.lives, START_LIVES);
+        assert_eq!(world.gs.stunned, 0.0);
+        assert_eq!(world.drain_events().collect::<Vec<_>>(), vec![GameEvent::Shielded]);
+        assert!(!world.gs.power_ups.active(PowerUp::Shield));
+
+        catch(&mut world, Kind::Hazard);
+        assert_eq!(world.gs.lives, START_LIVES - 1);
+    }
+
+    #[test]
+    fn power_ups_run_side_by_side_and_end_with_the_run() {
+        let mut world = playing(0);
+        catch(&mut world, Kind::PowerUp(PowerUp::Magnet));
+        catch(&mut world, Kind::PowerUp(PowerUp::Shield));
+        let running: Vec<PowerUp> = world.gs.power_ups.running().map(|(p, _)| p).collect();
+        assert_eq!(running, vec![PowerUp::Magnet, PowerUp::Shield]);
+        assert!(world.gs.scene.render(&world).text.contains("Magnet 8.0s"));
+
+        world.start_run();
+        assert_eq!(world.gs.power_ups.running().count(), 0);
+    }
+
+    #[test]
+    fn streaks_raise_the_multiplier_until_a_miss() {
+        let mut world = playing(0);
+        for _ in 0..COMBO_STEP {
+            catch(&mut world, Kind::Acorn);
+        }
+        // four at x1, then the fifth at x2
+        assert_eq!(world.gs.score, COMBO_STEP as usize + 1);
+        assert_eq!(world.gs.combo, COMBO_STEP);
+
+        park_squirrel(&mut world);
+        world.falling.objects.clear();
+        world.falling.spawn(Kind::Acorn, &mut world.gs.rng);
+        world.falling.objects[0].body.screen_region[1] = 1.0;
+        world.step(&InputFrame::default());
+        assert_eq!(world.gs.combo, 0);
+        assert_eq!(combo_multiplier(1000), MAX_COMBO_MULTIPLIER);
+    }
+
+    #[test]
+    fn perfect_catches_earn_a_bonus() {
+        let mut world = playing(0);
+        world.squirrel.face_left();
+        world.falling.objects.clear();
+        world.falling.spawn(Kind::Acorn, &mut world.gs.rng);
+        // the middle of the acorn right over the squirrel's head
+        let [x, y, w, _] = world.squirrel.screen_region;
+        let acorn = &mut world.falling.objects[0].body;
+        acorn.screen_region[0] = x + w / 4.0 - acorn.screen_region[2] / 2.0;
+        acorn.screen_region[1] = y + 5.0;
+        world.step(&InputFrame::default());
+        assert_eq!(world.gs.score, 1 + PERFECT_BONUS);
+        assert!(world.drain_events().any(|e| e == GameEvent::Caught { points: 3, perfect: true }));
+    }
+
+    #[test]
+    fn popups_float_up_and_fade() {
+        let mut world = playing(0);
+        catch(&mut world, Kind::Acorn);
+        assert_eq!(world.popups.len(), 1);
+        assert_eq!(world.popups[0].text, "+1");
+        let y = world.popups[0].y;
+        world.step(&InputFrame::default());
+        assert!(world.popups[0].y > y);
+        assert!(world.popups[0].opacity() < 1.0);
+        for _ in 0..(popup::POPUP_SECONDS / DT) as usize {
+            world.step(&InputFrame::default());
+        }
+        assert!(world.popups.is_empty());
+    }
+
+    #[test]
+    fn good_runs_ask_for_a_name() {
+        let mut world = playing(9);
+        catch(&mut world, Kind::Acorn);
+        world.gs.lives = 1;
+        world.miss();
+        assert_eq!(world.gs.scene, Scene::EnterName);
+
+        for c in "Nutty\u{8}y\rMcNutface!!".chars() {
+            world.type_char(c);
+        }
+        assert_eq!(world.gs.chars_typed.chars().count(), MAX_NAME_LEN);
+        world.step(&InputFrame { start: true, ..Default::default() });
+        assert_eq!(world.gs.scene, Scene::GameOver);
+        assert!(world.drain_events().any(|e| e == GameEvent::HighScore));
+        assert_eq!(world.last_rank, Some(0));
+        let entry = &world.high_scores.entries[0];
+        assert_eq!((entry.name.as_str(), entry.score, entry.seed), ("NuttyMcNutfa", 1, 9));
+        assert!(world.gs.scene.render(&world).text.contains("> 1. NuttyMcNutfa"));
+
+        // typing does nothing outside of name entry
+        world.type_char('x');
+        assert_eq!(world.gs.chars_typed, "NuttyMcNutfa");
+    }
+
+    #[test]
+    fn catching_works_facing_either_way() {
+        for face_right in [false, true] {
+            let mut world = playing(3);
+            if face_right {
+                world.squirrel.face_right();
+            } else {
+                world.squirrel.face_left();
+            }
+            catch(&mut world, Kind::Acorn);
+            assert_eq!(world.gs.score, 1);
+
+            // the top of the tail sticks out past the hitbox
+            let drawn = Rect::from_region(world.squirrel.screen_region);
+            world.falling.objects.clear();
+            world.falling.spawn(Kind::Acorn, &mut world.gs.rng);
+            let acorn = &mut world.falling.objects[0].body;
+            acorn.screen_region[0] = drawn.center().0 - acorn.screen_region[2] / 2.0;
+            acorn.screen_region[1] = drawn.top() - 15.0;
+            world.step(&InputFrame::default());
+            assert_eq!(world.gs.score, 1);
+        }
+    }
+
+    // Playing through a level with these waves
+    fn playing_level(waves: &str) -> World {
+        let mut world = World::new(4);
+        let text = format!("(name: \"Test\", waves: [{}])", waves);
+        world.level = Some(Level::from_ron(&text).unwrap());
+        world.step(&InputFrame { start: true, ..Default::default() });
+        world
+    }
+
+    #[test]
+    fn waves_follow_each_other_to_the_end_of_the_level() {
+        let mut world = playing_level(
+            "(name: \"A\", fall_speed: 100.0, spawn_interval: 0.1, max_live: 5, x: (300.0, 310.0), goal: Catch(2)),
+             (name: \"B\", fall_speed: 100.0, spawn_interval: 0.1, max_live: 5, seconds: Some(1.0), goal: Survive)",
+        );
+        for _ in 0..30 {
+            world.step(&InputFrame::default());
+        }
+        // only spawning where the wave says
+        assert!(world.falling.objects.len() > 1);
+        assert!(world.falling.objects.iter().all(|f| (300.0..=310.0).contains(&f.body.screen_region[0])));
+        // the first wave was announced in the middle of the screen
+        let announced = world.popups.iter().find(|p| p.text.starts_with("Wave 1")).unwrap();
+        assert_eq!(announced.x, world.size.width / 2.0);
+
+        catch(&mut world, Kind::Acorn);
+        assert_eq!(world.gs.wave, 0);
+        catch(&mut world, Kind::Acorn);
+        assert_eq!(world.gs.wave, 1);
+        assert!(world.drain_events().any(|e| e == GameEvent::WaveCleared(0)));
+
+        // the last wave only asks to hang on for a second
+        for _ in 0..61 {
+            world.step(&InputFrame::default());
+        }
+        assert!(world.gs.cleared);
+        assert_ne!(world.gs.scene, Scene::Playing);
+        assert!(world.drain_events().any(|e| e == GameEvent::WaveCleared(1)));
+    }
+
+    #[test]
+    fn running_out_of_time_ends_the_run() {
+        let mut world = playing_level(
+            "(name: \"A\", fall_speed: 100.0, spawn_interval: 1.0, max_live: 1, seconds: Some(0.5), goal: Catch(50))",
+        );
+        for _ in 0..29 {
+            world.step(&InputFrame::default());
+        }
+        assert_eq!(world.gs.scene, Scene::Playing);
+        for _ in 0..2 {
+            world.step(&InputFrame::default());
+        }
+        assert_eq!(world.gs.scene, Scene::GameOver);
+        assert!(!world.gs.cleared);
+    }
+}